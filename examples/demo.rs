@@ -27,36 +27,35 @@ fn main() {
 
     // initialize a new model
     let (width, height) = first.to_rgb8().dimensions();
-    let window_size = 64; //size of the tracking window
+    let window_width = 64; // width of the tracking window
+    let window_height = 64; // height of the tracking window
     let psr_thresh = 7.0; // how high the psr must be before prediction is considered succesful.
-    let settings = MosseTrackerSettings {
-        window_size,
-        width,
-        height,
-        regularization: 0.001,
-        learning_rate: 0.05,
-        psr_threshold: psr_thresh,
-    };
+    let settings = MosseTrackerSettings::builder(width, height)
+        .window_size(window_width, window_height)
+        .psr_threshold(psr_thresh)
+        .build()
+        .unwrap();
     let desperation_threshold = 3; // how many frames the tracker should try to re-acquire the target until we consider it failed
-    let mut multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
+    let mut multi_tracker: MultiMosseTracker =
+        MultiMosseTracker::new(settings, desperation_threshold);
 
     // coordinates of the target objects to track in the intial frame
     let target_coords = vec![
-        (143, 766),
-        (232, 653),
-        (291, 731),
-        (1298, 664),
-        (479, 642),
-        (574, 629),
-        (666, 627),
-        (762, 609),
+        (143.0, 766.0),
+        (232.0, 653.0),
+        (291.0, 731.0),
+        (1298.0, 664.0),
+        (479.0, 642.0),
+        (574.0, 629.0),
+        (666.0, 627.0),
+        (762.0, 609.0),
     ];
 
     // Add all the targets  on the first image to the multitracker
-    let first_img = first.to_luma8();
-    for (i, coords) in target_coords.into_iter().enumerate() {
+    let first_img = first.to_rgb8();
+    for coords in target_coords {
         let start = Instant::now();
-        multi_tracker.add_or_replace_target(i as u32, coords, &first_img);
+        multi_tracker.add_target(coords, &first_img).unwrap();
         println!(
             "Added object on initial frame to multi-tracker in {} ms",
             start.elapsed().as_millis()
@@ -69,7 +68,7 @@ fn main() {
 
         // track the objects on the new frame
         let start = Instant::now();
-        let predictions = multi_tracker.track(&dyn_img.to_luma8());
+        let predictions = multi_tracker.track(&dyn_img.to_rgb8()).unwrap();
 
         println!(
             "Processed sample image no. {} in {} ms. Active trackers: {}.",
@@ -93,13 +92,12 @@ fn main() {
                 pred.location.0 as i32,
                 pred.location.1 as i32,
             );
+            // top left corner of the tracking window
+            let left = (pred.location.0 - (window_width / 2) as f32) as i32;
+            let top = (pred.location.1 - (window_height / 2) as f32) as i32;
             draw_hollow_rect_mut(
                 &mut img_copy,
-                Rect::at(
-                    pred.location.0.saturating_sub(window_size / 2) as i32,
-                    pred.location.1.saturating_sub(window_size / 2) as i32,
-                )
-                .of_size(window_size, window_size),
+                Rect::at(left.max(0), top.max(0)).of_size(window_width, window_height),
                 color,
             );
 
@@ -112,8 +110,8 @@ fn main() {
             draw_text_mut(
                 &mut img_copy,
                 Rgba([125u8, 255u8, 0u8, 0u8]),
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2)).try_into().unwrap(),
+                left,
+                top,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("#{}", obj_id),
@@ -123,10 +121,8 @@ fn main() {
             draw_text_mut(
                 &mut img_copy,
                 color,
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2) + FONT_SCALE as u32)
-                    .try_into()
-                    .unwrap(),
+                left,
+                top + FONT_SCALE as i32,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("PSR: {:.2}", pred.psr),
@@ -141,6 +137,7 @@ fn main() {
             // save the filters
             multi_tracker
                 .dump_filter_reals()
+                .unwrap()
                 .iter()
                 .enumerate()
                 .for_each(|(i, f)| {
@@ -2,7 +2,7 @@ mod trax_protocol;
 
 use std::io::stdin;
 
-use mosse::{MosseTrackerSettings, MultiMosseTracker};
+use mosse::{MosseTrackerSettings, MultiMosseTracker, ScaleEstimationSettings};
 
 use crate::trax_protocol::{
     ChannelType, Image, ImageType, Region, RegionType, TraxMessageFromClient, TraxMessageFromServer,
@@ -13,7 +13,7 @@ pub enum ServerState {
     Introduction,
     Initialization,
     Reporting {
-        multi_tracker: MultiMosseTracker,
+        multi_tracker: Box<MultiMosseTracker>,
         first_region: Region,
     },
     Termination,
@@ -69,37 +69,34 @@ impl MosseTraxServer {
 
         // initialize a new model
         let (width, height) = first.to_rgb8().dimensions();
-        // FIXME: This tracks a square that entirely encloses the target region, so it may fixate
-        // on the background for tall or wide targets.
-        let window_size = f64::max(region.width, region.height) as u32; // size of the tracking window
-        let psr_thresh = 7.0; // how high the psr must be before prediction is considered succesful.
-        let settings = MosseTrackerSettings {
-            window_size,
-            width,
-            height,
-            regularization: 0.001,
-            learning_rate: 0.05,
-            psr_threshold: psr_thresh,
-        };
+        // the tracking window has the same shape as the target region
+        let window_width = region.width as u32;
+        let window_height = region.height as u32;
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(window_width, window_height)
+            .scale_estimation(ScaleEstimationSettings::default())
+            .build()
+            .unwrap();
 
         // FIXME: Could I get away with a single MosseTracker here? This would make things simpler,
         // but wouldn't change the results of the benchmark.
         let desperation_threshold = 300000; // how many frames the tracker should try to re-acquire the target until we consider it failed
-        let mut multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
+        let mut multi_tracker: MultiMosseTracker =
+            MultiMosseTracker::new(settings, desperation_threshold);
 
         let coords = (
-            (region.x + region.width / 2.) as u32,
-            (region.y + region.height / 2.) as u32,
+            (region.x + region.width / 2.) as f32,
+            (region.y + region.height / 2.) as f32,
         );
-        multi_tracker.add_or_replace_target(0, coords, &first.to_luma8());
+        multi_tracker
+            .add_or_replace_target(0, coords, &first.to_rgb8())
+            .unwrap();
 
         self.state = ServerState::Reporting {
-            multi_tracker,
+            multi_tracker: Box::new(multi_tracker),
             first_region: region.clone(),
         };
 
-        // if we were being honest, we would return the square region that we've
-        // actually fed into the model, but it probably doesn't matter that much.
         TraxMessageFromServer::State { region }
     }
 
@@ -122,21 +119,18 @@ impl MosseTraxServer {
         };
 
         let frame = &images[0].open().unwrap();
-        let predictions = multi_tracker.track(&frame.to_luma8());
+        let predictions = multi_tracker.track(&frame.to_rgb8()).unwrap();
         assert_eq!(predictions.len(), 1);
         let (_obj_id, pred) = &predictions[0];
 
+        // the region grows and shrinks with the estimated scale of the target
+        let width = first_region.width * pred.scale as f64;
+        let height = first_region.height * pred.scale as f64;
         let region = Region {
-            x: pred
-                .location
-                .0
-                .saturating_sub((first_region.width / 2.) as u32) as f64,
-            y: pred
-                .location
-                .1
-                .saturating_sub((first_region.height / 2.) as u32) as f64,
-            height: first_region.height,
-            width: first_region.width,
+            x: (pred.location.0 as f64 - width / 2.).max(0.),
+            y: (pred.location.1 as f64 - height / 2.).max(0.),
+            height,
+            width,
         };
 
         #[cfg(debug_assertions)]
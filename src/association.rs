@@ -0,0 +1,294 @@
+// Association of the boxes of an external detector with the targets of a MultiMosseTracker, in the style of SORT.
+//
+// Detectors are usually too slow to run on every frame, so the trackers follow the targets in between detections.
+// On a frame with detections, call track() first, and then associate() with the detections of that frame:
+// - detections are matched one to one with the active targets by the Hungarian algorithm, minimizing the total
+//   overlap cost, and matches that do not overlap enough are rejected
+// - matched targets are moved to the center of their detection, and their trackers learn the target there instead
+//   of where they tracked it, so the learning rate is the same on every frame
+// - matched targets that were lost are recovered
+// - every unmatched detection becomes a new target, under a newly allocated ID, so the IDs must be SequentialIds
+// - targets that go unmatched on more than max_misses detection frames in a row are removed
+// Paused targets take no part in the association.
+
+use crate::duplicates::iou;
+use crate::{
+    check_frame_size, Frame, MosseError, MultiMosseTracker, Overlap, RemovalReason, SequentialId,
+    TrackerEvent,
+};
+
+// a detected box, with its top left corner and dimensions in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Detection {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Detection {
+    pub fn center(&self) -> (f32, f32) {
+        (self.left + self.width / 2.0, self.top + self.height / 2.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssociationSettings {
+    // when a detection and a target match. The IoU is that of the detection and the same box around the target, as
+    // the tracking window of a target need not have the size of its detections.
+    pub overlap: Overlap,
+    // the number of detection frames in a row on which a target may go unmatched before it is removed
+    pub max_misses: u32,
+}
+
+// What associate() did with the detections, which are referred to by their index
+#[derive(Debug, Clone, PartialEq)]
+pub struct Associations<Id = u32> {
+    // the detections that were matched with a target
+    pub matched: Vec<(usize, Id)>,
+    // the detections that became new targets
+    pub spawned: Vec<(usize, Id)>,
+    // the targets that were removed because they went unmatched for too long
+    pub aged_out: Vec<Id>,
+}
+
+impl<Id: SequentialId> MultiMosseTracker<Id> {
+    pub fn associate<F: Frame>(
+        &mut self,
+        frame: &F,
+        detections: &[Detection],
+        settings: &AssociationSettings,
+    ) -> Result<Associations<Id>, MosseError> {
+        check_frame_size(frame, self.settings.width, self.settings.height)?;
+
+        // the cost of every pair of an active target and a detection, and whether it overlaps enough to match
+        let candidates: Vec<usize> = (0..self.trackers.len())
+            .filter(|&index| !self.trackers[index].paused)
+            .collect();
+        let costs: Vec<Vec<(f32, bool)>> = candidates
+            .iter()
+            .map(|&index| {
+                let location = self.trackers[index].tracker.current_target_center;
+                detections
+                    .iter()
+                    .map(|detection| match_cost(location, detection, settings.overlap))
+                    .collect()
+            })
+            .collect();
+        let assignment = hungarian(
+            &costs
+                .iter()
+                .map(|row| row.iter().map(|(cost, _)| *cost).collect())
+                .collect::<Vec<Vec<f32>>>(),
+        );
+
+        let mut associations = Associations {
+            matched: Vec::new(),
+            spawned: Vec::new(),
+            aged_out: Vec::new(),
+        };
+        let mut detected = vec![false; detections.len()];
+        let mut detected_points = Vec::new();
+        let mut events = Vec::new();
+        for (row, &index) in candidates.iter().enumerate() {
+            let target = &mut self.trackers[index];
+            match assignment[row].filter(|&column| costs[row][column].1) {
+                Some(column) => {
+                    let location = detections[column].center();
+                    detected[column] = true;
+                    target.misses = 0;
+                    if target.death_watch > 0 {
+                        events.push(TrackerEvent::TargetRecovered {
+                            id: target.id,
+                            frame: self.frame_count,
+                            location,
+                        });
+                    }
+                    target.death_watch = 0;
+                    target
+                        .tracker
+                        .correct_location(frame, self.frame_count, location);
+                    associations.matched.push((column, target.id));
+                    detected_points.push((target.id, location));
+                }
+                None => {
+                    target.misses += 1;
+                    if target.misses > settings.max_misses {
+                        associations.aged_out.push(target.id);
+                    }
+                }
+            }
+        }
+
+        for (id, location) in detected_points {
+            self.record_location(id, location);
+        }
+        for event in events {
+            self.emit(event);
+        }
+        for &id in &associations.aged_out {
+            self.trackers.retain(|target| target.id != id);
+            self.emit(TrackerEvent::TargetRemoved {
+                id,
+                frame: self.frame_count,
+                reason: RemovalReason::Unmatched,
+            });
+        }
+        for (column, detection) in detections.iter().enumerate() {
+            if !detected[column] {
+                let id = self.add_target(detection.center(), frame)?;
+                associations.spawned.push((column, id));
+            }
+        }
+        Ok(associations)
+    }
+}
+
+// the cost of a target without a location, far above the costs of actual matches
+const UNMATCHABLE: f32 = 1e9;
+
+// The cost of matching a target at the given location with a detection, which is lower for better matches, and
+// whether they overlap enough to be matched at all
+fn match_cost(location: (f32, f32), detection: &Detection, overlap: Overlap) -> (f32, bool) {
+    let (x, y) = location;
+    let (cost, matches) = match overlap {
+        Overlap::CenterDistance { max_distance } => {
+            let (center_x, center_y) = detection.center();
+            let distance = ((x - center_x).powi(2) + (y - center_y).powi(2)).sqrt();
+            (distance, distance <= max_distance)
+        }
+        Overlap::Iou { min_iou } => {
+            let moved = (
+                x - detection.width / 2.0,
+                y - detection.height / 2.0,
+                detection.width,
+                detection.height,
+            );
+            let detected = (
+                detection.left,
+                detection.top,
+                detection.width,
+                detection.height,
+            );
+            let iou = iou(moved, detected);
+            (1.0 - iou, iou >= min_iou)
+        }
+    };
+    // a target that has been lost can be anywhere, but the costs must stay finite
+    if cost.is_finite() {
+        (cost, matches)
+    } else {
+        (UNMATCHABLE, false)
+    }
+}
+
+// Assign every row of a cost matrix to a different column, minimizing the total cost, with the Hungarian algorithm.
+// Rows are left unassigned only if there are more rows than columns.
+fn hungarian(costs: &[Vec<f32>]) -> Vec<Option<usize>> {
+    let rows = costs.len();
+    let columns = costs.first().map_or(0, |row| row.len());
+    if rows > columns {
+        // assign the columns to the rows instead
+        let transposed: Vec<Vec<f32>> = (0..columns)
+            .map(|column| costs.iter().map(|row| row[column]).collect())
+            .collect();
+        let mut assignment = vec![None; rows];
+        for (column, row) in hungarian(&transposed).into_iter().enumerate() {
+            if let Some(row) = row {
+                assignment[row] = Some(column);
+            }
+        }
+        return assignment;
+    }
+
+    // the potentials of the rows and columns, and the row assigned to every column, with the rows and columns
+    // numbered from 1, and 0 standing for none
+    let mut row_potential = vec![0.0f64; rows + 1];
+    let mut column_potential = vec![0.0f64; columns + 1];
+    let mut assigned_row = vec![0usize; columns + 1];
+    let mut previous_column = vec![0usize; columns + 1];
+    for row in 1..=rows {
+        // find the shortest augmenting path from the new row to a free column
+        assigned_row[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![f64::INFINITY; columns + 1];
+        let mut visited = vec![false; columns + 1];
+        loop {
+            visited[column] = true;
+            let current_row = assigned_row[column];
+            let mut delta = f64::INFINITY;
+            let mut next_column = 0;
+            for other in 1..=columns {
+                if visited[other] {
+                    continue;
+                }
+                let slack = costs[current_row - 1][other - 1] as f64
+                    - row_potential[current_row]
+                    - column_potential[other];
+                if slack < min_slack[other] {
+                    min_slack[other] = slack;
+                    previous_column[other] = column;
+                }
+                if min_slack[other] < delta {
+                    delta = min_slack[other];
+                    next_column = other;
+                }
+            }
+            for other in 0..=columns {
+                if visited[other] {
+                    row_potential[assigned_row[other]] += delta;
+                    column_potential[other] -= delta;
+                } else {
+                    min_slack[other] -= delta;
+                }
+            }
+            column = next_column;
+            if assigned_row[column] == 0 {
+                break;
+            }
+        }
+
+        // flip the assignments along the path
+        while column != 0 {
+            let previous = previous_column[column];
+            assigned_row[column] = assigned_row[previous];
+            column = previous;
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for column in 1..=columns {
+        if assigned_row[column] != 0 {
+            assignment[assigned_row[column] - 1] = Some(column - 1);
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hungarian;
+
+    #[test]
+    fn hungarian_assignment() {
+        // the greedy choice for the first row is not the best
+        let costs = vec![
+            vec![1.0, 2.0, 3.0],
+            vec![2.0, 4.0, 6.0],
+            vec![3.0, 6.0, 9.0],
+        ];
+        assert_eq!(hungarian(&costs), vec![Some(2), Some(1), Some(0)]);
+
+        let costs = vec![vec![4.0, 1.0, 4.0], vec![2.0, 0.0, 5.0]];
+        assert_eq!(hungarian(&costs), vec![Some(1), Some(0)]);
+
+        // with more rows than columns, the rows that cost the most are left out
+        let costs = vec![vec![5.0], vec![1.0], vec![3.0]];
+        assert_eq!(hungarian(&costs), vec![None, Some(0), None]);
+
+        assert!(hungarian(&[]).is_empty());
+        assert_eq!(hungarian(&[vec![], vec![]]), vec![None, None]);
+    }
+}
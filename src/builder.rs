@@ -0,0 +1,261 @@
+// A builder for MosseTrackerSettings that starts from sensible defaults, and validates the settings when they are built.
+
+use crate::{
+    Augmentation, BorderMode, FeatureExtractor, Intensity, Kernel, MosseError,
+    MosseTrackerSettings, MotionModel, RedetectionSettings, ScaleEstimationSettings,
+};
+use std::sync::Arc;
+
+// The defaults follow the MOSSE paper where it gives a value, and the examples in this repository otherwise.
+pub const DEFAULT_WINDOW_SIZE: u32 = 64;
+pub const DEFAULT_LEARNING_RATE: f32 = 0.05;
+pub const DEFAULT_PSR_THRESHOLD: f32 = 7.0;
+pub const DEFAULT_REGULARIZATION: f32 = 0.001;
+pub const DEFAULT_TARGET_SIGMA: f32 = 1.0;
+pub const DEFAULT_PSR_EXCLUSION_SIZE: u32 = 11;
+
+#[derive(Debug)]
+pub struct MosseTrackerSettingsBuilder {
+    settings: MosseTrackerSettings,
+}
+
+impl MosseTrackerSettings {
+    // Start building the settings for frames of the given dimensions. Unless they are changed, the settings are:
+    // - a tracking window of DEFAULT_WINDOW_SIZE by DEFAULT_WINDOW_SIZE pixels, or the frame size if that is smaller
+    // - DEFAULT_LEARNING_RATE, DEFAULT_PSR_THRESHOLD, DEFAULT_REGULARIZATION, DEFAULT_TARGET_SIGMA and
+    //   DEFAULT_PSR_EXCLUSION_SIZE
+    // - a linear filter on raw intensities, without scale estimation, motion model or re-detection
+    // - a detection window of the size of the tracking window, that replicates the border of the frame
+    // - the default augmentation of the first window
+    pub fn builder(width: u32, height: u32) -> MosseTrackerSettingsBuilder {
+        MosseTrackerSettingsBuilder {
+            settings: MosseTrackerSettings {
+                width,
+                height,
+                window_width: DEFAULT_WINDOW_SIZE.min(width),
+                window_height: DEFAULT_WINDOW_SIZE.min(height),
+                learning_rate: DEFAULT_LEARNING_RATE,
+                psr_threshold: DEFAULT_PSR_THRESHOLD,
+                regularization: DEFAULT_REGULARIZATION,
+                scale_estimation: None,
+                kernel: None,
+                features: Arc::new(Intensity),
+                search_area_factor: 1.0,
+                border_mode: BorderMode::default(),
+                motion_model: None,
+                redetection: None,
+                target_sigma: DEFAULT_TARGET_SIGMA,
+                psr_exclusion_size: DEFAULT_PSR_EXCLUSION_SIZE,
+                augmentation: Augmentation::default(),
+            },
+        }
+    }
+}
+
+impl MosseTrackerSettingsBuilder {
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.settings.window_width = width;
+        self.settings.window_height = height;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: f32) -> Self {
+        self.settings.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn psr_threshold(mut self, psr_threshold: f32) -> Self {
+        self.settings.psr_threshold = psr_threshold;
+        self
+    }
+
+    pub fn regularization(mut self, regularization: f32) -> Self {
+        self.settings.regularization = regularization;
+        self
+    }
+
+    pub fn scale_estimation(mut self, scale_estimation: ScaleEstimationSettings) -> Self {
+        self.settings.scale_estimation = Some(scale_estimation);
+        self
+    }
+
+    pub fn kernel(mut self, kernel: Kernel) -> Self {
+        self.settings.kernel = Some(kernel);
+        self
+    }
+
+    pub fn features(mut self, features: Arc<dyn FeatureExtractor>) -> Self {
+        self.settings.features = features;
+        self
+    }
+
+    pub fn search_area_factor(mut self, search_area_factor: f32) -> Self {
+        self.settings.search_area_factor = search_area_factor;
+        self
+    }
+
+    pub fn border_mode(mut self, border_mode: BorderMode) -> Self {
+        self.settings.border_mode = border_mode;
+        self
+    }
+
+    pub fn motion_model(mut self, motion_model: MotionModel) -> Self {
+        self.settings.motion_model = Some(motion_model);
+        self
+    }
+
+    pub fn redetection(mut self, redetection: RedetectionSettings) -> Self {
+        self.settings.redetection = Some(redetection);
+        self
+    }
+
+    pub fn target_sigma(mut self, target_sigma: f32) -> Self {
+        self.settings.target_sigma = target_sigma;
+        self
+    }
+
+    pub fn psr_exclusion_size(mut self, psr_exclusion_size: u32) -> Self {
+        self.settings.psr_exclusion_size = psr_exclusion_size;
+        self
+    }
+
+    pub fn augmentation(mut self, augmentation: Augmentation) -> Self {
+        self.settings.augmentation = augmentation;
+        self
+    }
+
+    pub fn build(self) -> Result<MosseTrackerSettings, MosseError> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+}
+
+impl MosseTrackerSettings {
+    // Check that the settings are within their valid ranges: a non-empty frame, a tracking window of at least 2 by 2
+    // pixels that fits in the frame, a learning rate in (0, 1], a positive regularization, a positive target sigma,
+    // a non-zero PSR exclusion size and a search area factor of at least 1. The features must have a non-zero cell
+    // size that fits at least twice in the tracking window, and the optional parts of the tracker are checked too:
+    // at least one scale with a step above 1, a positive Gaussian kernel sigma or a non-zero polynomial exponent,
+    // a positive measurement noise and a non-negative process noise for the motion model, and a re-detection region
+    // that does not shrink.
+    pub fn validate(&self) -> Result<(), MosseError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(MosseError::InvalidFrameSize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if self.window_width < 2
+            || self.window_height < 2
+            || self.window_width > self.width
+            || self.window_height > self.height
+        {
+            return Err(MosseError::InvalidWindowSize {
+                width: self.window_width,
+                height: self.window_height,
+            });
+        }
+
+        let cell_size = self.features.cell_size();
+        let mut checks = vec![
+            (
+                "learning rate",
+                self.learning_rate > 0.0 && self.learning_rate <= 1.0,
+                "must lie in (0, 1]",
+            ),
+            (
+                "regularization",
+                self.regularization > 0.0 && self.regularization.is_finite(),
+                "must be positive",
+            ),
+            (
+                "target sigma",
+                self.target_sigma > 0.0 && self.target_sigma.is_finite(),
+                "must be positive",
+            ),
+            (
+                "PSR exclusion size",
+                self.psr_exclusion_size > 0,
+                "must be non-zero",
+            ),
+            (
+                "search area factor",
+                self.search_area_factor >= 1.0,
+                "must be at least 1",
+            ),
+            (
+                "feature cell size",
+                cell_size > 0
+                    && cell_size <= self.window_width / 2
+                    && cell_size <= self.window_height / 2,
+                "must be non-zero and fit at least twice in the tracking window",
+            ),
+        ];
+        if let Some(scale_estimation) = &self.scale_estimation {
+            checks.extend([
+                (
+                    "number of scales",
+                    scale_estimation.number_of_scales > 0,
+                    "must be non-zero",
+                ),
+                (
+                    "scale step",
+                    scale_estimation.scale_step > 1.0 && scale_estimation.scale_step.is_finite(),
+                    "must be larger than 1",
+                ),
+                (
+                    "scale learning rate",
+                    scale_estimation.learning_rate > 0.0 && scale_estimation.learning_rate <= 1.0,
+                    "must lie in (0, 1]",
+                ),
+            ]);
+        }
+        match self.kernel {
+            Some(Kernel::Gaussian { sigma }) => checks.push((
+                "kernel sigma",
+                sigma > 0.0 && sigma.is_finite(),
+                "must be positive",
+            )),
+            Some(Kernel::Polynomial { additive, exponent }) => checks.extend([
+                ("kernel additive", additive.is_finite(), "must be finite"),
+                ("kernel exponent", exponent > 0, "must be non-zero"),
+            ]),
+            None => {}
+        }
+        if let Some(
+            MotionModel::ConstantVelocity {
+                process_noise,
+                measurement_noise,
+            }
+            | MotionModel::ConstantAcceleration {
+                process_noise,
+                measurement_noise,
+            },
+        ) = self.motion_model
+        {
+            checks.extend([
+                (
+                    "process noise",
+                    process_noise >= 0.0 && process_noise.is_finite(),
+                    "must be non-negative",
+                ),
+                (
+                    "measurement noise",
+                    measurement_noise > 0.0 && measurement_noise.is_finite(),
+                    "must be positive",
+                ),
+            ]);
+        }
+        if let Some(redetection) = &self.redetection {
+            checks.push((
+                "growth factor",
+                redetection.growth_factor >= 1.0 && redetection.growth_factor.is_finite(),
+                "must be at least 1",
+            ));
+        }
+        match checks.into_iter().find(|(_, valid, _)| !valid) {
+            Some((name, _, reason)) => Err(MosseError::InvalidSetting { name, reason }),
+            None => Ok(()),
+        }
+    }
+}
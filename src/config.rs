@@ -0,0 +1,245 @@
+// The configuration of a MultiMosseTracker: the settings of its trackers and when to give up on a target.
+//
+// With the serde feature, configurations can be loaded from (and saved to) TOML and JSON.
+// Settings that are left out of a file take the defaults of MosseTrackerSettings::builder(), and the settings are
+// validated when they are loaded. For example:
+//
+//     desperation_level = 5
+//     history_capacity = 100
+//
+//     [settings]
+//     width = 1280
+//     height = 720
+//     psr_threshold = 8.0
+//     features = { type = "hog", cell_size = 4 }
+//     kernel = { type = "gaussian", sigma = 0.5 }
+//
+//     [settings.augmentation]
+//     rotations = [0.05, -0.05]
+//     scales = []
+//
+//     [dormant_pool]
+//     capacity = 8
+//     max_age = 300
+//
+//     [duplicate_suppression]
+//     overlap = { type = "iou", min_iou = 0.5 }
+//     policy = "drop_lower_psr"
+
+#[cfg(feature = "serde")]
+use crate::MosseError;
+use crate::{
+    DormantPoolSettings, DuplicateSuppression, MosseTrackerSettings, MultiMosseTracker, TargetId,
+};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackerConfig {
+    pub settings: MosseTrackerSettings,
+    // how many times a tracker is allowed to fail the PSR threshold before it expires
+    pub desperation_level: u32,
+    // keeps expired trackers around to re-acquire their targets if set
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub dormant_pool: Option<DormantPoolSettings>,
+    // drops one of every two targets that overlap if set
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub duplicate_suppression: Option<DuplicateSuppression>,
+    // the number of points kept in the history of every target, none by default
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub history_capacity: usize,
+}
+
+impl<Id: TargetId> MultiMosseTracker<Id> {
+    // Create a multi-tracker from a configuration, for targets with IDs of any type, like
+    // `MultiMosseTracker::<u64>::from_config(config)`.
+    pub fn from_config(config: TrackerConfig) -> MultiMosseTracker<Id> {
+        let dormant_pool = config.dormant_pool.unwrap_or(DormantPoolSettings {
+            capacity: 0,
+            max_age: None,
+        });
+        let mut multi_tracker = MultiMosseTracker::with_dormant_pool(
+            config.settings,
+            config.desperation_level,
+            dormant_pool,
+        );
+        multi_tracker.duplicate_suppression = config.duplicate_suppression;
+        multi_tracker.history_capacity = config.history_capacity;
+        multi_tracker
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TrackerConfig {
+    pub fn from_toml_str(toml: &str) -> Result<TrackerConfig, MosseError> {
+        toml::from_str(toml).map_err(|e| MosseError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn from_json_str(json: &str) -> Result<TrackerConfig, MosseError> {
+        serde_json::from_str(json).map_err(|e| MosseError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<TrackerConfig, MosseError> {
+        TrackerConfig::from_toml_str(&read_config_file(path.as_ref())?)
+    }
+
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<TrackerConfig, MosseError> {
+        TrackerConfig::from_json_str(&read_config_file(path.as_ref())?)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, MosseError> {
+        toml::to_string(self).map_err(|e| MosseError::InvalidConfig(e.to_string()))
+    }
+
+    pub fn to_json_string(&self) -> Result<String, MosseError> {
+        serde_json::to_string_pretty(self).map_err(|e| MosseError::InvalidConfig(e.to_string()))
+    }
+}
+
+#[cfg(feature = "serde")]
+fn read_config_file(path: &Path) -> Result<String, MosseError> {
+    std::fs::read_to_string(path)
+        .map_err(|e| MosseError::InvalidConfig(format!("{}: {}", path.display(), e)))
+}
+
+// The settings as they are stored in a file. Settings that are left out take the defaults of the builder.
+#[cfg(feature = "serde")]
+mod settings_file {
+    use crate::{
+        Augmentation, BorderMode, FeatureType, Kernel, MosseError, MosseTrackerSettings,
+        MotionModel, RedetectionSettings, ScaleEstimationSettings, DEFAULT_WINDOW_SIZE,
+    };
+    use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct SettingsFile {
+        width: u32,
+        height: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window_width: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window_height: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        learning_rate: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        psr_threshold: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        regularization: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scale_estimation: Option<ScaleEstimationSettings>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kernel: Option<Kernel>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        features: Option<FeatureType>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        search_area_factor: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        border_mode: Option<BorderMode>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        motion_model: Option<MotionModel>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        redetection: Option<RedetectionSettings>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        target_sigma: Option<f32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        psr_exclusion_size: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        augmentation: Option<Augmentation>,
+    }
+
+    impl Serialize for MosseTrackerSettings {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let features = self.features.feature_type().ok_or_else(|| {
+                ser::Error::custom("custom feature extractors cannot be serialized")
+            })?;
+            SettingsFile {
+                width: self.width,
+                height: self.height,
+                window_width: Some(self.window_width),
+                window_height: Some(self.window_height),
+                learning_rate: Some(self.learning_rate),
+                psr_threshold: Some(self.psr_threshold),
+                regularization: Some(self.regularization),
+                scale_estimation: self.scale_estimation,
+                kernel: self.kernel,
+                features: Some(features),
+                search_area_factor: Some(self.search_area_factor),
+                border_mode: Some(self.border_mode),
+                motion_model: self.motion_model,
+                redetection: self.redetection,
+                target_sigma: Some(self.target_sigma),
+                psr_exclusion_size: Some(self.psr_exclusion_size),
+                augmentation: Some(self.augmentation.clone()),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for MosseTrackerSettings {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let file = SettingsFile::deserialize(deserializer)?;
+            file.into_settings().map_err(de::Error::custom)
+        }
+    }
+
+    impl SettingsFile {
+        fn into_settings(self) -> Result<MosseTrackerSettings, MosseError> {
+            let window_width = self
+                .window_width
+                .unwrap_or(DEFAULT_WINDOW_SIZE.min(self.width));
+            let window_height = self
+                .window_height
+                .unwrap_or(DEFAULT_WINDOW_SIZE.min(self.height));
+            let mut builder = MosseTrackerSettings::builder(self.width, self.height)
+                .window_size(window_width, window_height);
+            if let Some(learning_rate) = self.learning_rate {
+                builder = builder.learning_rate(learning_rate);
+            }
+            if let Some(psr_threshold) = self.psr_threshold {
+                builder = builder.psr_threshold(psr_threshold);
+            }
+            if let Some(regularization) = self.regularization {
+                builder = builder.regularization(regularization);
+            }
+            if let Some(scale_estimation) = self.scale_estimation {
+                builder = builder.scale_estimation(scale_estimation);
+            }
+            if let Some(kernel) = self.kernel {
+                builder = builder.kernel(kernel);
+            }
+            if let Some(features) = self.features {
+                builder = builder.features(features.extractor());
+            }
+            if let Some(search_area_factor) = self.search_area_factor {
+                builder = builder.search_area_factor(search_area_factor);
+            }
+            if let Some(border_mode) = self.border_mode {
+                builder = builder.border_mode(border_mode);
+            }
+            if let Some(motion_model) = self.motion_model {
+                builder = builder.motion_model(motion_model);
+            }
+            if let Some(redetection) = self.redetection {
+                builder = builder.redetection(redetection);
+            }
+            if let Some(target_sigma) = self.target_sigma {
+                builder = builder.target_sigma(target_sigma);
+            }
+            if let Some(psr_exclusion_size) = self.psr_exclusion_size {
+                builder = builder.psr_exclusion_size(psr_exclusion_size);
+            }
+            if let Some(augmentation) = self.augmentation {
+                builder = builder.augmentation(augmentation);
+            }
+            builder.build()
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// Suppression of duplicate tracks, when two trackers of a MultiMosseTracker have drifted onto the same object.
+//
+// After every frame, the windows of the active targets are compared pairwise. Of every pair that overlaps, one
+// target is dropped according to the policy, and a DuplicateSuppressed event names the target that was dropped and
+// the one it duplicated, followed by a TargetRemoved event for the dropped target. Whatever the policy, a target
+// that was missed on this frame never wins over one that was not, as it is about to expire. Paused targets are
+// left alone.
+
+use crate::{
+    MosseTracker, MultiMosseTracker, Prediction, RemovalReason, Target, TargetId, TrackerEvent,
+};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateSuppression {
+    pub overlap: Overlap,
+    pub policy: DuplicatePolicy,
+}
+
+// When two targets are considered duplicates
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum Overlap {
+    // the centers of the targets are at most this many pixels apart
+    CenterDistance { max_distance: f32 },
+    // the intersection over union of the (scaled) tracking windows is at least this large
+    Iou { min_iou: f32 },
+}
+
+// Which of two duplicate targets is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DuplicatePolicy {
+    // keep the target whose tracker had the higher PSR on this frame
+    DropLowerPsr,
+    // keep the target that has been active the longest
+    KeepOlder,
+    // Keep the target that has been active the longest, and merge the other one into it: the filters they learned
+    // are averaged, and the kept target carries on from the location, scale and motion of the tracker that had the
+    // higher PSR on this frame. Trackers with other window sizes, features, kernels or scale estimation learned
+    // filters that cannot be averaged, and then the kept target carries on with the better tracker as it is.
+    Merge,
+}
+
+impl<Id: TargetId> MultiMosseTracker<Id> {
+    pub fn set_duplicate_suppression(
+        &mut self,
+        duplicate_suppression: Option<DuplicateSuppression>,
+    ) {
+        self.duplicate_suppression = duplicate_suppression;
+    }
+
+    // Drop the duplicates among the active targets, and their predictions. Targets are in the order in which they
+    // became active, so of every pair the first is the older one.
+    pub(crate) fn suppress_duplicates(
+        &mut self,
+        predictions: &mut Vec<(Id, Prediction)>,
+        events: &mut Vec<TrackerEvent<Id>>,
+    ) {
+        let Some(suppression) = self.duplicate_suppression else {
+            return;
+        };
+
+        let mut older = 0;
+        while older < self.trackers.len() {
+            let mut newer = older + 1;
+            while newer < self.trackers.len() {
+                let (a, b) = (&self.trackers[older], &self.trackers[newer]);
+                if a.paused || b.paused || !overlaps(a, b, suppression.overlap) {
+                    newer += 1;
+                    continue;
+                }
+
+                let (a_missed, b_missed) = (a.death_watch > 0, b.death_watch > 0);
+                let b_is_better = match (a_missed, b_missed) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => b.tracker.last_psr > a.tracker.last_psr,
+                };
+                let dropped = match suppression.policy {
+                    DuplicatePolicy::DropLowerPsr if b_is_better => older,
+                    DuplicatePolicy::KeepOlder if a_missed && !b_missed => older,
+                    DuplicatePolicy::DropLowerPsr | DuplicatePolicy::KeepOlder => newer,
+                    DuplicatePolicy::Merge => {
+                        let (kept, dropped) = self.trackers.split_at_mut(newer);
+                        let (a, b) = (&mut kept[older], &mut dropped[0]);
+                        if a.tracker.merge(&b.tracker) {
+                            if b_is_better {
+                                a.tracker.follow(&b.tracker);
+                            }
+                        } else if b_is_better {
+                            std::mem::swap(&mut a.tracker, &mut b.tracker);
+                        }
+                        if b_is_better {
+                            // the older target carries on with the prediction of the better tracker
+                            a.death_watch = b.death_watch;
+                            let (a_id, b_id) = (a.id, b.id);
+                            let prediction = take_prediction(predictions, b_id);
+                            if let (Some(prediction), Some(kept)) = (
+                                prediction,
+                                predictions.iter_mut().find(|(id, _)| *id == a_id),
+                            ) {
+                                kept.1 = prediction;
+                            }
+                        }
+                        newer
+                    }
+                };
+
+                let Target { id, .. } = self.trackers.remove(dropped);
+                let kept = self.trackers[if dropped == older { newer - 1 } else { older }].id;
+                take_prediction(predictions, id);
+                events.push(TrackerEvent::DuplicateSuppressed {
+                    id,
+                    duplicate_of: kept,
+                    frame: self.frame_count,
+                });
+                events.push(TrackerEvent::TargetRemoved {
+                    id,
+                    frame: self.frame_count,
+                    reason: RemovalReason::Duplicate,
+                });
+                if dropped == older {
+                    // compare the target that took its place with the rest
+                    newer = older + 1;
+                }
+            }
+            older += 1;
+        }
+    }
+}
+
+impl MosseTracker {
+    // Average the filters this tracker and another tracker of the same target have learned, if they fit together.
+    // Returns whether they did.
+    pub(crate) fn merge(&mut self, other: &MosseTracker) -> bool {
+        let fits = self.window_width == other.window_width
+            && self.window_height == other.window_height
+            && Arc::ptr_eq(&self.features, &other.features)
+            && self.settings.kernel == other.settings.kernel
+            && self.settings.scale_estimation == other.settings.scale_estimation;
+        if !fits {
+            return false;
+        }
+
+        for (top, other_top) in self.last_top.iter_mut().zip(&other.last_top) {
+            average(top, other_top);
+        }
+        average(&mut self.last_bottom, &other.last_bottom);
+        self.solve_linear_filter();
+        if let (Some(kernel_filter), Some(other_kernel_filter)) =
+            (&mut self.kernel_filter, &other.kernel_filter)
+        {
+            average(&mut kernel_filter.alpha, &other_kernel_filter.alpha);
+            for (template, other_template) in kernel_filter
+                .template
+                .iter_mut()
+                .zip(&other_kernel_filter.template)
+            {
+                average(template, other_template);
+            }
+            for (spectrum, other_spectrum) in kernel_filter
+                .template_spectrum
+                .iter_mut()
+                .zip(&other_kernel_filter.template_spectrum)
+            {
+                average(spectrum, other_spectrum);
+            }
+        }
+        if let (Some(scale_filter), Some(other_scale_filter)) =
+            (&mut self.scale_filter, &other.scale_filter)
+        {
+            for (top, other_top) in scale_filter.top.iter_mut().zip(&other_scale_filter.top) {
+                average(top, other_top);
+            }
+            average(&mut scale_filter.bottom, &other_scale_filter.bottom);
+        }
+
+        // what was learned on this frame can no longer be replaced by a detection
+        self.replaceable_frame = None;
+        true
+    }
+
+    // carry on from where another tracker of the same target found it
+    fn follow(&mut self, other: &MosseTracker) {
+        self.current_target_center = other.current_target_center;
+        self.current_scale = other.current_scale;
+        self.kalman_filter = other.kalman_filter.clone();
+        self.last_psr = other.last_psr;
+        self.missed_frames = other.missed_frames;
+    }
+}
+
+// replace the values with the average of them and the other values
+fn average<T>(values: &mut [T], others: &[T])
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Mul<f32, Output = T>,
+{
+    for (value, other) in values.iter_mut().zip(others) {
+        *value = (*value + *other) * 0.5;
+    }
+}
+
+fn take_prediction<Id: TargetId>(
+    predictions: &mut Vec<(Id, Prediction)>,
+    id: Id,
+) -> Option<Prediction> {
+    let index = predictions.iter().position(|(other, _)| *other == id)?;
+    Some(predictions.remove(index).1)
+}
+
+fn overlaps<Id>(a: &Target<Id>, b: &Target<Id>, overlap: Overlap) -> bool {
+    let (ax, ay) = a.tracker.current_target_center;
+    let (bx, by) = b.tracker.current_target_center;
+    match overlap {
+        Overlap::CenterDistance { max_distance } => {
+            ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt() <= max_distance
+        }
+        Overlap::Iou { min_iou } => iou(window_box(a), window_box(b)) >= min_iou,
+    }
+}
+
+// the tracking window of a target around its center, grown or shrunk with the target, as (left, top, width, height)
+fn window_box<Id>(target: &Target<Id>) -> (f32, f32, f32, f32) {
+    let tracker = &target.tracker;
+    let width = tracker.window_width as f32 * tracker.current_scale;
+    let height = tracker.window_height as f32 * tracker.current_scale;
+    let (x, y) = tracker.current_target_center;
+    (x - width / 2.0, y - height / 2.0, width, height)
+}
+
+// the intersection over union of two boxes given as (left, top, width, height)
+pub(crate) fn iou(a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)) -> f32 {
+    let width = (a.0 + a.2).min(b.0 + b.2) - a.0.max(b.0);
+    let height = (a.1 + a.3).min(b.1 + b.3) - a.1.max(b.1);
+    if width <= 0.0 || height <= 0.0 {
+        return 0.0;
+    }
+    let intersection = width * height;
+    intersection / (a.2 * a.3 + b.2 * b.3 - intersection)
+}
@@ -0,0 +1,88 @@
+// The errors returned by the trackers.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MosseError {
+    // the frame dimensions in the settings are zero
+    InvalidFrameSize {
+        width: u32,
+        height: u32,
+    },
+    // the dimensions of the tracking window are zero
+    InvalidWindowSize {
+        width: u32,
+        height: u32,
+    },
+    // a setting is out of its valid range
+    InvalidSetting {
+        name: &'static str,
+        reason: &'static str,
+    },
+    // a configuration could not be read, parsed or written
+    InvalidConfig(String),
+    // a checkpoint could not be read or written
+    InvalidCheckpoint(String),
+    // a checkpoint was written in a format version this version of the library cannot read
+    UnsupportedCheckpointVersion(u32),
+    // a frame does not have the dimensions the tracker was configured with
+    FrameSizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    // a buffer does not hold exactly one value for every pixel of an image
+    BufferSizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    // there is no target with this ID, which is formatted with Debug
+    UnknownTarget(String),
+    // every ID of the ID type has been allocated
+    IdentifiersExhausted,
+    // reading or writing a file failed
+    Io(String),
+    // a line of a MOTChallenge file could not be parsed, with lines numbered from 1
+    InvalidMotLine {
+        line: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for MosseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MosseError::InvalidFrameSize { width, height } => {
+                write!(f, "invalid frame size {}x{}", width, height)
+            }
+            MosseError::InvalidWindowSize { width, height } => {
+                write!(f, "invalid tracking window size {}x{}", width, height)
+            }
+            MosseError::InvalidSetting { name, reason } => {
+                write!(f, "invalid {}: {}", name, reason)
+            }
+            MosseError::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            MosseError::InvalidCheckpoint(message) => write!(f, "invalid checkpoint: {}", message),
+            MosseError::UnsupportedCheckpointVersion(version) => {
+                write!(f, "unsupported checkpoint version {}", version)
+            }
+            MosseError::FrameSizeMismatch { expected, actual } => write!(
+                f,
+                "expected a frame of {}x{}, got {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            MosseError::BufferSizeMismatch { expected, actual } => write!(
+                f,
+                "expected a buffer of {} values, got {}",
+                expected, actual
+            ),
+            MosseError::UnknownTarget(id) => write!(f, "unknown target {}", id),
+            MosseError::IdentifiersExhausted => write!(f, "no target IDs left to allocate"),
+            MosseError::Io(message) => write!(f, "I/O error: {}", message),
+            MosseError::InvalidMotLine { line, reason } => {
+                write!(f, "invalid MOTChallenge line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MosseError {}
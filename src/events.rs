@@ -0,0 +1,78 @@
+// Events in the lifecycle of the targets of a MultiMosseTracker, so callers can tell when and why a track ended.
+//
+// Events are passed to the handler registered with MultiMosseTracker::set_event_handler(), as they happen.
+// Frames are numbered from 1 by every call to track(); events outside of track() carry the number of the last frame.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackerEvent<Id = u32> {
+    // the tracker of the target missed the PSR threshold, or its target left the frame, after a successful frame
+    TargetLost {
+        id: Id,
+        frame: u64,
+        location: (f32, f32),
+    },
+    // a lost target was found again, by its active tracker or by its dormant tracker
+    TargetRecovered {
+        id: Id,
+        frame: u64,
+        location: (f32, f32),
+    },
+    // the target is no longer tracked, and its tracker is gone
+    TargetRemoved {
+        id: Id,
+        frame: u64,
+        reason: RemovalReason,
+    },
+    // a new target was added under the ID of an existing one, whose tracker was discarded
+    TargetReplaced {
+        id: Id,
+        frame: u64,
+        location: (f32, f32),
+    },
+    // the target was dropped because it overlapped with another target, which is tracked on. It is followed by a
+    // TargetRemoved event for the dropped target.
+    DuplicateSuppressed {
+        id: Id,
+        duplicate_of: Id,
+        frame: u64,
+    },
+}
+
+impl<Id: Copy> TrackerEvent<Id> {
+    pub fn id(&self) -> Id {
+        match *self {
+            TrackerEvent::TargetLost { id, .. }
+            | TrackerEvent::TargetRecovered { id, .. }
+            | TrackerEvent::TargetRemoved { id, .. }
+            | TrackerEvent::TargetReplaced { id, .. }
+            | TrackerEvent::DuplicateSuppressed { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    // the tracker missed the PSR threshold desperation_level times in a row, and was not kept in the dormant pool,
+    // or it stayed in the dormant pool for longer than its maximum age
+    Expired,
+    // the dormant tracker was discarded to make room for more recently expired trackers
+    Evicted,
+    // the target was removed with MultiMosseTracker::remove_target()
+    Removed,
+    // the target was not matched with a detection on too many detection frames in a row, see associate()
+    Unmatched,
+    // the target duplicated another target, see set_duplicate_suppression()
+    Duplicate,
+}
+
+type HandlerFn<Id> = dyn FnMut(&TrackerEvent<Id>) + Send;
+
+pub(crate) struct EventHandler<Id>(pub(crate) Box<HandlerFn<Id>>);
+
+impl<Id> fmt::Debug for EventHandler<Id> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventHandler")
+    }
+}
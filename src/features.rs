@@ -0,0 +1,307 @@
+// Feature extraction: turns a cropped window into one or more channels of features that the correlation filter
+// is trained on. The filter correlates every channel separately, and sums the results.
+
+use crate::log_normalize;
+use image::{imageops, RgbImage};
+use std::f32;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// Extracts a stack of feature channels from a window.
+// Windows are always RGB, also when they were cropped from a grayscale frame.
+//
+// Every channel covers the window on a grid of (window_width / cell_size) by (window_height / cell_size) cells,
+// stored row by row. The tracker multiplies every channel by a cosine window afterwards.
+// Features should be normalized to roughly zero mean and unit norm over all channels together,
+// so filter and kernel parameters do not depend on the window size or the feature type.
+pub trait FeatureExtractor: Debug + Send + Sync {
+    // the number of channels returned by extract()
+    fn channels(&self) -> usize;
+
+    // the width and height of a cell in window pixels
+    fn cell_size(&self) -> u32;
+
+    fn extract(&self, window: &RgbImage) -> Vec<Vec<f32>>;
+
+    // the built-in extractor this is, if any, so it can be saved with the tracker settings
+    fn feature_type(&self) -> Option<FeatureType> {
+        None
+    }
+}
+
+// The built-in feature extractors and their parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum FeatureType {
+    Intensity,
+    Hog { cell_size: u32 },
+    OpponentColor { cell_size: u32 },
+}
+
+impl FeatureType {
+    pub fn extractor(&self) -> Arc<dyn FeatureExtractor> {
+        match *self {
+            FeatureType::Intensity => Arc::new(Intensity),
+            FeatureType::Hog { cell_size } => Arc::new(Hog { cell_size }),
+            FeatureType::OpponentColor { cell_size } => Arc::new(OpponentColor { cell_size }),
+        }
+    }
+}
+
+// A single channel of log-transformed, normalized pixel intensities, as used in the MOSSE paper.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Intensity;
+
+impl FeatureExtractor for Intensity {
+    fn channels(&self) -> usize {
+        1
+    }
+
+    fn cell_size(&self) -> u32 {
+        1
+    }
+
+    fn extract(&self, window: &RgbImage) -> Vec<Vec<f32>> {
+        vec![log_normalize(&imageops::grayscale(window))]
+    }
+
+    fn feature_type(&self) -> Option<FeatureType> {
+        Some(FeatureType::Intensity)
+    }
+}
+
+// the number of contrast-insensitive orientation bins. There are twice as many contrast-sensitive bins.
+const HOG_ORIENTATIONS: usize = 9;
+
+// normalized histogram values are truncated at this value
+const HOG_TRUNCATION: f32 = 0.2;
+
+// Histogram of oriented gradients, following Felzenszwalb et al. (2010).
+// Object Detection with Discriminatively Trained Part Based Models.
+//
+// Every cell has 31 channels: 18 contrast-sensitive orientations, 9 contrast-insensitive orientations and
+// 4 texture features that capture the gradient energy around the cell.
+// The gradient of every pixel is taken from the colour channel in which it is strongest.
+#[derive(Debug, Clone, Copy)]
+pub struct Hog {
+    pub cell_size: u32,
+}
+
+impl Default for Hog {
+    fn default() -> Self {
+        Hog { cell_size: 4 }
+    }
+}
+
+impl FeatureExtractor for Hog {
+    fn channels(&self) -> usize {
+        3 * HOG_ORIENTATIONS + 4
+    }
+
+    fn cell_size(&self) -> u32 {
+        self.cell_size
+    }
+
+    fn extract(&self, window: &RgbImage) -> Vec<Vec<f32>> {
+        let (width, height) = window.dimensions();
+        let cell_size = self.cell_size.max(1);
+        let cells_x = (width / cell_size).max(1) as usize;
+        let cells_y = (height / cell_size).max(1) as usize;
+        let sensitive_bins = 2 * HOG_ORIENTATIONS;
+
+        // build the contrast-sensitive orientation histogram of every cell
+        let mut histograms = vec![0f32; cells_x * cells_y * sensitive_bins];
+        let pixel = |x: i64, y: i64, c: usize| {
+            let x = x.clamp(0, width as i64 - 1) as u32;
+            let y = y.clamp(0, height as i64 - 1) as u32;
+            window.get_pixel(x, y)[c] as f32
+        };
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let (dx, dy, magnitude) = (0..3)
+                    .map(|c| {
+                        let dx = pixel(x + 1, y, c) - pixel(x - 1, y, c);
+                        let dy = pixel(x, y + 1, c) - pixel(x, y - 1, c);
+                        (dx, dy, (dx * dx + dy * dy).sqrt())
+                    })
+                    .fold((0.0, 0.0, 0.0), |best, gradient| {
+                        if gradient.2 > best.2 {
+                            gradient
+                        } else {
+                            best
+                        }
+                    });
+                if magnitude == 0.0 {
+                    continue;
+                }
+
+                // snap the gradient orientation to the nearest contrast-sensitive bin
+                let angle = dy.atan2(dx).rem_euclid(2.0 * f32::consts::PI);
+                let bin = (angle / (2.0 * f32::consts::PI) * sensitive_bins as f32).round()
+                    as usize
+                    % sensitive_bins;
+
+                // distribute the magnitude over the four nearest cells with bilinear interpolation
+                let cell_x = (x as f32 + 0.5) / cell_size as f32 - 0.5;
+                let cell_y = (y as f32 + 0.5) / cell_size as f32 - 0.5;
+                let x0 = cell_x.floor();
+                let y0 = cell_y.floor();
+                let weight_x1 = cell_x - x0;
+                let weight_y1 = cell_y - y0;
+                for (cx, weight_x) in [(x0 as i64, 1.0 - weight_x1), (x0 as i64 + 1, weight_x1)] {
+                    for (cy, weight_y) in [(y0 as i64, 1.0 - weight_y1), (y0 as i64 + 1, weight_y1)]
+                    {
+                        if cx < 0 || cy < 0 || cx >= cells_x as i64 || cy >= cells_y as i64 {
+                            continue;
+                        }
+                        let cell = cy as usize * cells_x + cx as usize;
+                        histograms[cell * sensitive_bins + bin] += weight_x * weight_y * magnitude;
+                    }
+                }
+            }
+        }
+
+        // the gradient energy of every cell, based on the contrast-insensitive histogram
+        let energy: Vec<f32> = histograms
+            .chunks_exact(sensitive_bins)
+            .map(|histogram| {
+                (0..HOG_ORIENTATIONS)
+                    .map(|o| histogram[o] + histogram[o + HOG_ORIENTATIONS])
+                    .map(|v| v * v)
+                    .sum()
+            })
+            .collect();
+        let cell_energy = |cx: i64, cy: i64| {
+            let cx = cx.clamp(0, cells_x as i64 - 1) as usize;
+            let cy = cy.clamp(0, cells_y as i64 - 1) as usize;
+            energy[cy * cells_x + cx]
+        };
+
+        let mut features = vec![vec![0f32; cells_x * cells_y]; self.channels()];
+        for cy in 0..cells_y {
+            for cx in 0..cells_x {
+                let cell = cy * cells_x + cx;
+                let histogram = &histograms[cell * sensitive_bins..(cell + 1) * sensitive_bins];
+
+                // normalize by the energy of each of the four 2x2 blocks of cells that contain this cell
+                let normalizers: Vec<f32> = [(-1, -1), (0, -1), (-1, 0), (0, 0)]
+                    .iter()
+                    .map(|(ox, oy)| {
+                        let (bx, by) = (cx as i64 + ox, cy as i64 + oy);
+                        let block_energy = cell_energy(bx, by)
+                            + cell_energy(bx + 1, by)
+                            + cell_energy(bx, by + 1)
+                            + cell_energy(bx + 1, by + 1);
+                        1.0 / (block_energy + f32::EPSILON).sqrt()
+                    })
+                    .collect();
+
+                let mut texture = [0f32; 4];
+                for (o, value) in histogram.iter().enumerate() {
+                    let mut sum = 0.0;
+                    for (t, n) in normalizers.iter().enumerate() {
+                        let normalized = (value * n).min(HOG_TRUNCATION);
+                        sum += normalized;
+                        texture[t] += normalized;
+                    }
+                    features[o][cell] = 0.5 * sum;
+                }
+
+                for o in 0..HOG_ORIENTATIONS {
+                    let value = histogram[o] + histogram[o + HOG_ORIENTATIONS];
+                    let sum: f32 = normalizers
+                        .iter()
+                        .map(|n| (value * n).min(HOG_TRUNCATION))
+                        .sum();
+                    features[sensitive_bins + o][cell] = 0.5 * sum;
+                }
+
+                for (t, value) in texture.iter().enumerate() {
+                    features[3 * HOG_ORIENTATIONS + t][cell] = 0.2357 * value;
+                }
+            }
+        }
+
+        normalize_channels(&mut features);
+        features
+    }
+
+    fn feature_type(&self) -> Option<FeatureType> {
+        Some(FeatureType::Hog {
+            cell_size: self.cell_size,
+        })
+    }
+}
+
+// Opponent colour channels: two chromatic channels, red-green and yellow-blue, and an intensity channel.
+// See van de Sande et al. (2010). Evaluating Color Descriptors for Object and Scene Recognition.
+//
+// The channels are averaged over cells of cell_size by cell_size pixels.
+#[derive(Debug, Clone, Copy)]
+pub struct OpponentColor {
+    pub cell_size: u32,
+}
+
+impl Default for OpponentColor {
+    fn default() -> Self {
+        OpponentColor { cell_size: 1 }
+    }
+}
+
+impl FeatureExtractor for OpponentColor {
+    fn channels(&self) -> usize {
+        3
+    }
+
+    fn cell_size(&self) -> u32 {
+        self.cell_size
+    }
+
+    fn extract(&self, window: &RgbImage) -> Vec<Vec<f32>> {
+        let (width, height) = window.dimensions();
+        let cell_size = self.cell_size.max(1);
+        let cells_x = (width / cell_size).max(1);
+        let cells_y = (height / cell_size).max(1);
+        let cell_area = (cell_size * cell_size) as f32;
+
+        let mut features = vec![vec![0f32; (cells_x * cells_y) as usize]; self.channels()];
+        for (x, y, pixel) in window.enumerate_pixels() {
+            let (cx, cy) = (x / cell_size, y / cell_size);
+            if cx >= cells_x || cy >= cells_y {
+                continue;
+            }
+            let cell = (cy * cells_x + cx) as usize;
+
+            let [r, g, b] = pixel.0.map(|v| v as f32 / 255.0);
+            features[0][cell] += (r - g) / f32::consts::SQRT_2 / cell_area;
+            features[1][cell] += (r + g - 2.0 * b) / 6f32.sqrt() / cell_area;
+            features[2][cell] += (r + g + b) / 3f32.sqrt() / cell_area;
+        }
+
+        normalize_channels(&mut features);
+        features
+    }
+
+    fn feature_type(&self) -> Option<FeatureType> {
+        Some(FeatureType::OpponentColor {
+            cell_size: self.cell_size,
+        })
+    }
+}
+
+// normalize a stack of channels to zero mean (per channel) and unit norm (over all channels), if possible
+fn normalize_channels(channels: &mut [Vec<f32>]) {
+    for channel in channels.iter_mut() {
+        let mean = channel.iter().sum::<f32>() / channel.len() as f32;
+        channel.iter_mut().for_each(|v| *v -= mean);
+    }
+
+    let norm = channels.iter().flatten().map(|v| v * v).sum::<f32>().sqrt();
+    if norm != 0.0 {
+        channels.iter_mut().flatten().for_each(|v| *v /= norm);
+    }
+}
@@ -0,0 +1,305 @@
+// Two-dimensional FFT over row-major image buffers, composed of one-dimensional row and column passes.
+//
+// The input of the forward transform is always real (preprocessed pixel data, or the target output map),
+// so its spectrum is Hermitian-symmetric. We only compute and store the non-redundant half of it:
+// width / 2 + 1 columns by height rows. The row passes are real-to-complex (and complex-to-real) transforms,
+// the column passes are ordinary complex transforms over the remaining half of the columns.
+//
+// Planning an FFT is relatively expensive, and all trackers that share a window size can share the same plans.
+// The plans are therefore cached per window size for the lifetime of the process. Only the sizes that trackers are
+// configured with are cached: the sizes of the regions searched during re-detection vary from frame to frame, and
+// caching their plans would make the cache grow without bound.
+
+use realfft::{ComplexToReal, RealFftPlanner, RealToComplex};
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{Fft, FftPlanner};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+// plans for every window size that has been used so far
+type PlanCache = Mutex<HashMap<(u32, u32), Arc<Fft2d>>>;
+
+pub(crate) struct Fft2d {
+    width: usize,
+    height: usize,
+
+    // thread-safe FFT objects containing precomputed parameters for the rows and columns of the window.
+    row_fft: Arc<dyn RealToComplex<f32>>,
+    row_inv_fft: Arc<dyn ComplexToReal<f32>>,
+    col_fft: Arc<dyn Fft<f32>>,
+    col_inv_fft: Arc<dyn Fft<f32>>,
+}
+
+// the cached plans, locked
+fn plans() -> MutexGuard<'static, HashMap<(u32, u32), Arc<Fft2d>>> {
+    static PLANS: OnceLock<PlanCache> = OnceLock::new();
+    PLANS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        // the cache holds no invariants that a panicking thread could have broken
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Fft2d {
+    // Get the (cached) 2D FFT plans for a window of the given size.
+    pub(crate) fn new(width: u32, height: u32) -> Arc<Fft2d> {
+        plans()
+            .entry((width, height))
+            .or_insert_with(|| Arc::new(Fft2d::uncached(width, height)))
+            .clone()
+    }
+
+    // Plan the 2D FFT for a window of the given size, without caching it, for sizes that are used only briefly.
+    pub(crate) fn uncached(width: u32, height: u32) -> Fft2d {
+        let mut real_planner = RealFftPlanner::new();
+        let mut planner = FftPlanner::new();
+        Fft2d {
+            width: width as usize,
+            height: height as usize,
+            row_fft: real_planner.plan_fft_forward(width as usize),
+            row_inv_fft: real_planner.plan_fft_inverse(width as usize),
+            col_fft: planner.plan_fft_forward(height as usize),
+            col_inv_fft: planner.plan_fft_inverse(height as usize),
+        }
+    }
+
+    // the number of columns in the half spectrum
+    fn half_width(&self) -> usize {
+        self.width / 2 + 1
+    }
+
+    // the number of elements in the half spectrum
+    pub(crate) fn spectrum_len(&self) -> usize {
+        self.half_width() * self.height
+    }
+
+    // the number of elements in the (real) input
+    pub(crate) fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    // Copy a real buffer of the size of a smaller transform into the center of a zero-filled buffer of this size.
+    pub(crate) fn pad_centered(&self, input: &[f32], source: &Fft2d) -> Vec<f32> {
+        let left = self.width / 2 - source.width / 2;
+        let top = self.height / 2 - source.height / 2;
+        self.pad(input, source, |x, y| (x + left, y + top))
+    }
+
+    // Copy a real buffer of the size of a smaller transform into a zero-filled buffer of this size.
+    // The buffer is treated as circular around its origin: the second half of each dimension is moved to the end.
+    pub(crate) fn pad_circular(&self, input: &[f32], source: &Fft2d) -> Vec<f32> {
+        let wrap = |i: usize, source_len: usize, len: usize| {
+            if i < source_len.div_ceil(2) {
+                i
+            } else {
+                len - (source_len - i)
+            }
+        };
+        self.pad(input, source, |x, y| {
+            (
+                wrap(x, source.width, self.width),
+                wrap(y, source.height, self.height),
+            )
+        })
+    }
+
+    fn pad(
+        &self,
+        input: &[f32],
+        source: &Fft2d,
+        position: impl Fn(usize, usize) -> (usize, usize),
+    ) -> Vec<f32> {
+        debug_assert!(source.width <= self.width && source.height <= self.height);
+
+        let mut output = vec![0.0; self.len()];
+        for (y, row) in input.chunks_exact(source.width).enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                let (px, py) = position(x, y);
+                output[py * self.width + px] = *value;
+            }
+        }
+        output
+    }
+
+    // Forward transform of a real, row-major buffer of width * height elements into its half spectrum.
+    // NOTE: the input is used as scratch space, so its contents are garbage after this call.
+    pub(crate) fn forward(&self, input: &mut [f32]) -> Vec<Complex<f32>> {
+        debug_assert_eq!(input.len(), self.width * self.height);
+
+        let half_width = self.half_width();
+        let mut spectrum = vec![Complex::zero(); self.spectrum_len()];
+        let mut scratch = self.row_fft.make_scratch_vec();
+        for (row, row_spectrum) in input
+            .chunks_exact_mut(self.width)
+            .zip(spectrum.chunks_exact_mut(half_width))
+        {
+            // the buffer lengths are correct by construction, which is the only thing that can fail here
+            let _ = self
+                .row_fft
+                .process_with_scratch(row, row_spectrum, &mut scratch);
+        }
+
+        process_columns(&mut spectrum, half_width, self.height, &self.col_fft);
+
+        spectrum
+    }
+
+    // Inverse transform of a half spectrum back into a real, row-major buffer of width * height elements.
+    // Like rustfft, the output is not normalized: it is scaled by width * height.
+    // NOTE: the spectrum is used as scratch space, so its contents are garbage after this call.
+    pub(crate) fn inverse(&self, spectrum: &mut [Complex<f32>]) -> Vec<f32> {
+        debug_assert_eq!(spectrum.len(), self.spectrum_len());
+
+        let half_width = self.half_width();
+        process_columns(spectrum, half_width, self.height, &self.col_inv_fft);
+
+        let mut output = vec![0.0; self.width * self.height];
+        let mut scratch = self.row_inv_fft.make_scratch_vec();
+        for (row_spectrum, row) in spectrum
+            .chunks_exact_mut(half_width)
+            .zip(output.chunks_exact_mut(self.width))
+        {
+            // The imaginary parts of the first and last bins of each row should be zero, but may not be exactly
+            // zero due to rounding errors. They are ignored, and the transform is performed regardless.
+            let _ = self
+                .row_inv_fft
+                .process_with_scratch(row_spectrum, row, &mut scratch);
+        }
+
+        output
+    }
+}
+
+// transform the columns of a row-major buffer in place
+fn process_columns(
+    buffer: &mut [Complex<f32>],
+    width: usize,
+    height: usize,
+    col_fft: &Arc<dyn Fft<f32>>,
+) {
+    // transpose, so the columns become consecutive chunks, transform them and transpose back.
+    // rustfft transforms every consecutive chunk of the FFT length.
+    let mut transposed = transpose(buffer, width, height);
+    col_fft.process(&mut transposed);
+    buffer.copy_from_slice(&transpose(&transposed, height, width));
+}
+
+// transpose a row-major buffer of the given dimensions
+fn transpose(buffer: &[Complex<f32>], width: usize, height: usize) -> Vec<Complex<f32>> {
+    let mut transposed = Vec::with_capacity(buffer.len());
+    for x in 0..width {
+        for y in 0..height {
+            transposed.push(buffer[y * width + x]);
+        }
+    }
+    transposed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // the non-redundant half of the full 2D DFT
+    fn naive_half_dft2d(input: &[f32], width: usize, height: usize) -> Vec<Complex<f32>> {
+        let half_width = width / 2 + 1;
+        let mut output = vec![Complex::new(0.0, 0.0); half_width * height];
+        for v in 0..height {
+            for u in 0..half_width {
+                for y in 0..height {
+                    for x in 0..width {
+                        let angle = -2.0
+                            * PI
+                            * ((u * x) as f32 / width as f32 + (v * y) as f32 / height as f32);
+                        output[v * half_width + u] +=
+                            input[y * width + x] * Complex::new(angle.cos(), angle.sin());
+                    }
+                }
+            }
+        }
+        output
+    }
+
+    fn test_input(width: usize, height: usize) -> Vec<f32> {
+        (0..width * height)
+            .map(|i| ((i * 7) % 11) as f32 - 5.0)
+            .collect()
+    }
+
+    #[test]
+    fn forward_matches_naive_dft() {
+        for (width, height) in [(4, 4), (5, 3), (6, 8)] {
+            let input = test_input(width, height);
+            let spectrum = Fft2d::new(width as u32, height as u32).forward(&mut input.clone());
+            let expected = naive_half_dft2d(&input, width, height);
+
+            assert_eq!(spectrum.len(), expected.len());
+            for (i, (x, y)) in spectrum.iter().zip(&expected).enumerate() {
+                assert!((x - y).norm() < 1e-3, "index {}: {} != {}", i, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn uncached_plans() {
+        // a size that no other test uses
+        let (width, height) = (97, 89);
+        let input = test_input(width, height);
+        let spectrum = Fft2d::uncached(width as u32, height as u32).forward(&mut input.clone());
+        assert!(!plans().contains_key(&(width as u32, height as u32)));
+
+        let cached = Fft2d::new(width as u32, height as u32).forward(&mut input.clone());
+        assert_eq!(spectrum, cached);
+    }
+
+    #[test]
+    fn padding() {
+        let small = Fft2d::new(3, 2);
+        let large = Fft2d::new(5, 4);
+        let input: Vec<f32> = (1..=6).map(|v| v as f32).collect();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            large.pad_centered(&input, &small),
+            vec![
+                0.0, 0.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 2.0, 3.0, 0.0,
+                0.0, 4.0, 5.0, 6.0, 0.0,
+                0.0, 0.0, 0.0, 0.0, 0.0,
+            ]
+        );
+        #[rustfmt::skip]
+        assert_eq!(
+            large.pad_circular(&input, &small),
+            vec![
+                1.0, 2.0, 0.0, 0.0, 3.0,
+                0.0, 0.0, 0.0, 0.0, 0.0,
+                0.0, 0.0, 0.0, 0.0, 0.0,
+                4.0, 5.0, 0.0, 0.0, 6.0,
+            ]
+        );
+    }
+
+    #[test]
+    fn inverse_undoes_forward() {
+        for (width, height) in [(7, 4), (8, 6)] {
+            let input = test_input(width, height);
+            let fft = Fft2d::new(width as u32, height as u32);
+
+            let mut spectrum = fft.forward(&mut input.clone());
+            let output = fft.inverse(&mut spectrum);
+
+            for (i, (x, y)) in output.iter().zip(&input).enumerate() {
+                let expected = y * (width * height) as f32;
+                assert!(
+                    (x - expected).abs() < 1e-3,
+                    "index {}: {} != {}",
+                    i,
+                    x,
+                    expected
+                );
+            }
+        }
+    }
+}
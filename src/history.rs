@@ -0,0 +1,152 @@
+// The recent trajectories of the targets of a MultiMosseTracker.
+//
+// When enabled with a non-zero capacity, every target keeps the points of its last frames: the location the tracker
+// predicted on every call to track(), or where it was added or detected. The history of a target stays with it
+// while it is dormant, starts over when the target is replaced, and is gone when the target is removed.
+
+use crate::{MultiMosseTracker, Prediction, TargetId};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    // the number of the frame, counting calls to track() from 1
+    pub frame: u64,
+    pub location: (f32, f32),
+    pub psr: f32,
+    pub status: TrackStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TrackStatus {
+    // the tracker made the PSR threshold, with its target inside the frame
+    Tracked,
+    // the tracker missed the PSR threshold, or its target left the frame, so the location is unreliable
+    Missed,
+    // the location was given: the target was added there, or its location was corrected to a detection
+    Detected,
+}
+
+impl<Id: TargetId> MultiMosseTracker<Id> {
+    // Keep the last `capacity` points of every target, or no history at all for a capacity of 0, which is the
+    // default. Histories that are longer are cut short, dropping their oldest points.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        let histories = self
+            .trackers
+            .iter_mut()
+            .map(|target| &mut target.history)
+            .chain(self.dormant.iter_mut().map(|dormant| &mut dormant.history));
+        for history in histories {
+            let excess = history.len().saturating_sub(capacity);
+            history.drain(..excess);
+        }
+    }
+
+    // The points in the history of an active or dormant target, oldest first.
+    pub fn trajectory(&self, id: Id) -> Option<Vec<TrackPoint>> {
+        self.history(id)
+            .map(|history| history.iter().copied().collect())
+    }
+
+    // The last n points in the history of an active or dormant target, oldest first.
+    pub fn last_points(&self, id: Id, n: usize) -> Option<Vec<TrackPoint>> {
+        self.history(id).map(|history| {
+            let skip = history.len().saturating_sub(n);
+            history.iter().skip(skip).copied().collect()
+        })
+    }
+
+    // The length of the path through the points in the history of a target on which it was tracked or detected, in
+    // pixels. The points on which the tracker missed its target are left out.
+    pub fn path_length(&self, id: Id) -> Option<f32> {
+        self.history(id).map(|history| {
+            let found: Vec<(f32, f32)> = history
+                .iter()
+                .filter(|point| point.status != TrackStatus::Missed)
+                .map(|point| point.location)
+                .collect();
+            found
+                .windows(2)
+                .map(|pair| {
+                    ((pair[1].0 - pair[0].0).powi(2) + (pair[1].1 - pair[0].1).powi(2)).sqrt()
+                })
+                .sum()
+        })
+    }
+
+    fn history(&self, id: Id) -> Option<&VecDeque<TrackPoint>> {
+        self.trackers
+            .iter()
+            .find(|target| target.id == id)
+            .map(|target| &target.history)
+            .or_else(|| {
+                self.dormant
+                    .iter()
+                    .find(|dormant| dormant.id == id)
+                    .map(|dormant| &dormant.history)
+            })
+    }
+
+    // add the predictions of the current frame to the histories of their targets
+    pub(crate) fn record_predictions(&mut self, predictions: &[(Id, Prediction)]) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        for (id, prediction) in predictions {
+            let status = if prediction.missed_frames == 0 && !prediction.left_frame {
+                TrackStatus::Tracked
+            } else {
+                TrackStatus::Missed
+            };
+            self.record(
+                *id,
+                TrackPoint {
+                    frame: self.frame_count,
+                    location: prediction.location,
+                    psr: prediction.psr,
+                    status,
+                },
+            );
+        }
+    }
+
+    // add a point for a location that was given to the history of an active target
+    pub(crate) fn record_location(&mut self, id: Id, location: (f32, f32)) {
+        let psr = match self.trackers.iter().find(|target| target.id == id) {
+            Some(target) => target.tracker.last_psr,
+            None => return,
+        };
+        self.record(
+            id,
+            TrackPoint {
+                frame: self.frame_count,
+                location,
+                psr,
+                status: TrackStatus::Detected,
+            },
+        );
+    }
+
+    // add a point to the history of an active target, replacing the point of the same frame if there is one
+    fn record(&mut self, id: Id, point: TrackPoint) {
+        let capacity = self.history_capacity;
+        if capacity == 0 {
+            return;
+        }
+        if let Some(target) = self.trackers.iter_mut().find(|target| target.id == id) {
+            let history = &mut target.history;
+            if history.back().is_some_and(|last| last.frame == point.frame) {
+                history.pop_back();
+            }
+            if history.len() == capacity {
+                history.pop_front();
+            }
+            history.push_back(point);
+        }
+    }
+}
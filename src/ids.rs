@@ -0,0 +1,40 @@
+// The identifiers of the targets of a MultiMosseTracker.
+//
+// Any type that is Copy, Ord, Debug and Send can identify targets, like integers or UUIDs, with the IDs picked by
+// the caller. Types that implement SequentialId can also have the multi-tracker allocate them, with add_target().
+// Allocated IDs follow each other in increasing order, and skip past every ID the caller has used, so an ID that was
+// allocated was never used before.
+
+use std::fmt::Debug;
+
+pub trait TargetId: Copy + Ord + Debug + Send + 'static {}
+
+impl<T: Copy + Ord + Debug + Send + 'static> TargetId for T {}
+
+pub trait SequentialId: TargetId {
+    // the first ID to allocate
+    fn first() -> Self;
+
+    // the ID to allocate after this one, if there are any left
+    fn next(&self) -> Option<Self>;
+}
+
+impl SequentialId for u32 {
+    fn first() -> Self {
+        0
+    }
+
+    fn next(&self) -> Option<Self> {
+        self.checked_add(1)
+    }
+}
+
+impl SequentialId for u64 {
+    fn first() -> Self {
+        0
+    }
+
+    fn next(&self) -> Option<Self> {
+        self.checked_add(1)
+    }
+}
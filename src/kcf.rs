@@ -0,0 +1,241 @@
+// Kernelized correlation filter (KCF), as described in
+// Henriques et al. (2015). High-Speed Tracking with Kernelized Correlation Filters.
+//
+// Instead of learning a linear filter, the KCF learns a set of dual coefficients (alpha) over all cyclic shifts
+// of the training window, and compares new windows against a running model template through a kernel function.
+// The windowing, preprocessing and FFT machinery are the same as for the linear MOSSE filter.
+// Windows with multiple feature channels are compared over all channels at once: the kernel sums their dot products.
+
+use crate::fft::Fft2d;
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+
+// The kernel used to compare two windows.
+// Note that the preprocessed windows are normalized to (roughly) unit norm, so the squared distance between two windows
+// lies in [0, 4] and their dot product in [-1, 1], regardless of the window size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum Kernel {
+    // k(x, z) = exp(-|x - z|^2 / sigma^2). A sigma around 0.5 is a sensible starting point.
+    Gaussian { sigma: f32 },
+    // k(x, z) = (x . z + additive)^exponent. The KCF paper uses additive = 1 and a small exponent like 7.
+    Polynomial { additive: f32, exponent: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KernelizedFilter {
+    kernel: Kernel,
+
+    // constants: learning rate and regularization (lambda in the KCF paper)
+    eta: f32,
+    regularization: f32,
+
+    // the dual coefficients (half spectrum)
+    pub(crate) alpha: Vec<Complex<f32>>,
+
+    // the model template in the spatial domain, and its half spectrum, per feature channel
+    pub(crate) template: Vec<Vec<f32>>,
+    pub(crate) template_spectrum: Vec<Vec<Complex<f32>>>,
+}
+
+impl KernelizedFilter {
+    pub(crate) fn new(
+        kernel: Kernel,
+        learning_rate: f32,
+        regularization: f32,
+        spectrum_len: usize,
+    ) -> KernelizedFilter {
+        KernelizedFilter {
+            kernel,
+            eta: learning_rate,
+            regularization,
+            alpha: vec![Complex::zero(); spectrum_len],
+            template: Vec::new(),
+            template_spectrum: Vec::new(),
+        }
+    }
+
+    // Evaluate the kernel between x and all cyclic shifts of z, and return its half spectrum.
+    fn kernel_correlation(
+        &self,
+        fft: &Fft2d,
+        x: &[Vec<f32>],
+        x_spectrum: &[Vec<Complex<f32>>],
+        z: &[Vec<f32>],
+        z_spectrum: &[Vec<Complex<f32>>],
+    ) -> Vec<Complex<f32>> {
+        // the dot products of x with all cyclic shifts of z, computed in the fourier domain and summed over all channels
+        let mut xz_spectrum = vec![Complex::zero(); fft.spectrum_len()];
+        for (x_channel, z_channel) in x_spectrum.iter().zip(z_spectrum) {
+            xz_spectrum
+                .iter_mut()
+                .zip(x_channel.iter().zip(z_channel))
+                .for_each(|(xz, (x, z))| *xz += x.conj() * z);
+        }
+
+        // the inverse transform is not normalized
+        let n = x.first().map_or(1, |channel| channel.len()) as f32;
+        let xz = fft.inverse(&mut xz_spectrum).into_iter().map(|c| c / n);
+
+        let xx: f32 = x.iter().flatten().map(|v| v * v).sum();
+        let zz: f32 = z.iter().flatten().map(|v| v * v).sum();
+        let mut k: Vec<f32> = xz.map(|c| self.evaluate(xx, zz, c)).collect();
+
+        fft.forward(&mut k)
+    }
+
+    // evaluate the kernel, given the squared norms of both inputs and their dot product
+    fn evaluate(&self, xx: f32, zz: f32, xz: f32) -> f32 {
+        match self.kernel {
+            Kernel::Gaussian { sigma } => (-(xx + zz - 2.0 * xz).max(0.0) / (sigma * sigma)).exp(),
+            Kernel::Polynomial { additive, exponent } => (xz + additive).powi(exponent as i32),
+        }
+    }
+
+    // compute the dual coefficients for a single (preprocessed) window
+    fn solve(
+        &self,
+        fft: &Fft2d,
+        features: &[Vec<f32>],
+        spectrum: &[Vec<Complex<f32>>],
+        target: &[Complex<f32>],
+    ) -> Vec<Complex<f32>> {
+        let kxx = self.kernel_correlation(fft, features, spectrum, features, spectrum);
+
+        // note that we add a small quantity to avoid dividing by zero, which would yield NaN's.
+        target
+            .iter()
+            .zip(kxx)
+            .map(|(g, k)| g / (k + self.regularization))
+            .collect()
+    }
+
+    // the half spectrum of every feature channel
+    fn spectrum(fft: &Fft2d, features: &[Vec<f32>]) -> Vec<Vec<Complex<f32>>> {
+        // NOTE: the input of the forward transform is garbage after the call, so we clone the features first.
+        features
+            .iter()
+            .map(|channel| fft.forward(&mut channel.clone()))
+            .collect()
+    }
+
+    pub(crate) fn train(&mut self, fft: &Fft2d, features: Vec<Vec<f32>>, target: &[Complex<f32>]) {
+        let spectrum = Self::spectrum(fft, &features);
+
+        self.alpha = self.solve(fft, &features, &spectrum, target);
+        self.template = features;
+        self.template_spectrum = spectrum;
+    }
+
+    pub(crate) fn update(&mut self, fft: &Fft2d, features: Vec<Vec<f32>>, target: &[Complex<f32>]) {
+        let spectrum = Self::spectrum(fft, &features);
+        let new_alpha = self.solve(fft, &features, &spectrum, target);
+
+        // linearly interpolate both the dual coefficients and the model template
+        let one_minus_eta = 1.0 - self.eta;
+        self.alpha
+            .iter_mut()
+            .zip(new_alpha)
+            .for_each(|(prev, new)| *prev = self.eta * new + one_minus_eta * *prev);
+        self.template
+            .iter_mut()
+            .flatten()
+            .zip(features.into_iter().flatten())
+            .for_each(|(prev, new)| *prev = self.eta * new + one_minus_eta * *prev);
+        self.template_spectrum
+            .iter_mut()
+            .flatten()
+            .zip(spectrum.into_iter().flatten())
+            .for_each(|(prev, new)| *prev = self.eta * new + one_minus_eta * *prev);
+    }
+
+    // Compute the (real) correlation map of a preprocessed window with the model.
+    pub(crate) fn correlate(&self, fft: &Fft2d, features: Vec<Vec<f32>>) -> Vec<f32> {
+        let spectrum = Self::spectrum(fft, &features);
+        let kxz = self.kernel_correlation(
+            fft,
+            &self.template,
+            &self.template_spectrum,
+            &features,
+            &spectrum,
+        );
+
+        let mut response: Vec<Complex<f32>> =
+            self.alpha.iter().zip(kxz).map(|(a, k)| a * k).collect();
+
+        fft.inverse(&mut response)
+    }
+
+    // Compute the (real) correlation map of a preprocessed detection window that is larger than the template.
+    // Instead of comparing the template with cyclic shifts of the window, it is compared with every template-sized
+    // part of the window, by zero-padding the template and the dual coefficients to the size of the window.
+    pub(crate) fn correlate_padded(
+        &self,
+        fft: &Fft2d,
+        search_fft: &Fft2d,
+        features: Vec<Vec<f32>>,
+    ) -> Vec<f32> {
+        let n = search_fft.len() as f32;
+        let spectrum = Self::spectrum(search_fft, &features);
+
+        // the dot products of the template with every part of the window
+        let mut xz_spectrum = vec![Complex::zero(); search_fft.spectrum_len()];
+        for (template, z_channel) in self.template.iter().zip(&spectrum) {
+            let x_channel = search_fft.forward(&mut search_fft.pad_centered(template, fft));
+            xz_spectrum
+                .iter_mut()
+                .zip(x_channel.iter().zip(z_channel))
+                .for_each(|(xz, (x, z))| *xz += x.conj() * z);
+        }
+        let xz = search_fft.inverse(&mut xz_spectrum);
+
+        // the squared norms of every part of the window: the sum of the squared features over a template-sized box
+        let mut squares = vec![0f32; search_fft.len()];
+        for channel in &features {
+            squares
+                .iter_mut()
+                .zip(channel)
+                .for_each(|(sq, v)| *sq += v * v);
+        }
+        let box_spectrum =
+            search_fft.forward(&mut search_fft.pad_centered(&vec![1.0; fft.len()], fft));
+        let mut zz_spectrum: Vec<Complex<f32>> = box_spectrum
+            .iter()
+            .zip(search_fft.forward(&mut squares))
+            .map(|(b, sq)| b.conj() * sq)
+            .collect();
+        let zz = search_fft.inverse(&mut zz_spectrum);
+
+        // the inverse transforms are not normalized
+        let xx: f32 = self.template.iter().flatten().map(|v| v * v).sum();
+        let mut k: Vec<f32> = xz
+            .iter()
+            .zip(&zz)
+            .map(|(xz, zz)| self.evaluate(xx, zz / n, xz / n))
+            .collect();
+
+        // NOTE: the input of the inverse transform is garbage after the call, so we clone the coefficients first.
+        let alpha: Vec<f32> = fft
+            .inverse(&mut self.alpha.clone())
+            .into_iter()
+            .map(|a| a / fft.len() as f32)
+            .collect();
+        let alpha_spectrum = search_fft.forward(&mut search_fft.pad_centered(&alpha, fft));
+
+        let mut response: Vec<Complex<f32>> = alpha_spectrum
+            .iter()
+            .zip(search_fft.forward(&mut k))
+            .map(|(a, k)| a * k)
+            .collect();
+
+        search_fft.inverse(&mut response)
+    }
+
+    pub(crate) fn dual_coefficients(&self) -> &Vec<Complex<f32>> {
+        &self.alpha
+    }
+}
@@ -2,23 +2,63 @@ extern crate image;
 extern crate imageproc;
 extern crate rustfft;
 
-use image::{imageops, GrayImage, ImageBuffer, Luma};
+use image::imageops::FilterType;
+use image::{imageops, GenericImageView, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage};
 use imageproc::geometric_transformations::Projection;
 use imageproc::geometric_transformations::{rotate_about_center, warp, Interpolation};
 use rustfft::num_complex::Complex;
 use rustfft::num_traits::Zero;
-use rustfft::{Fft, FftPlanner};
 use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::f32;
 use std::fmt::Debug;
 use std::sync::Arc;
 
+mod association;
+mod builder;
+mod config;
+mod duplicates;
+mod error;
+mod events;
+mod features;
+mod fft;
+mod history;
+mod ids;
+mod kcf;
+mod mot;
+mod motion;
+mod scale;
+#[cfg(feature = "serde")]
+mod state;
 #[cfg(target_arch = "wasm32")]
 pub mod wasm;
 
+pub use association::{AssociationSettings, Associations, Detection};
+pub use builder::{
+    MosseTrackerSettingsBuilder, DEFAULT_LEARNING_RATE, DEFAULT_PSR_EXCLUSION_SIZE,
+    DEFAULT_PSR_THRESHOLD, DEFAULT_REGULARIZATION, DEFAULT_TARGET_SIGMA, DEFAULT_WINDOW_SIZE,
+};
+pub use config::TrackerConfig;
+pub use duplicates::{DuplicatePolicy, DuplicateSuppression, Overlap};
+pub use error::MosseError;
+use events::EventHandler;
+pub use events::{RemovalReason, TrackerEvent};
+pub use features::{FeatureExtractor, FeatureType, Hog, Intensity, OpponentColor};
+use fft::Fft2d;
+pub use history::{TrackPoint, TrackStatus};
+pub use ids::{SequentialId, TargetId};
+pub use kcf::Kernel;
+use kcf::KernelizedFilter;
+pub use mot::{read_mot, MotRecord, MotWriter};
+use motion::KalmanFilter;
+pub use motion::{MotionEstimate, MotionModel};
+pub use scale::ScaleEstimationSettings;
+use scale::ScaleFilter;
+#[cfg(feature = "serde")]
+pub use state::CHECKPOINT_VERSION;
+
 // TODO: use constant declarations wherever possible
 // TODO: refactor the unwrap statement into match statements wherever we can't be certain a result exists.
-// TODO: behaviour at edge of frame: target may not leave frame, but filter will screw up anyway due to cropping. Move target coord freely within template?
 // TODO: improve initial filter quality: additional affine perturbations, like scaling (zooming)?
 // TODO: 11x11 window around peak for PSR calculation is arbitrary and seems biased towards larger video feeds?
 // TODO: make k (number of perturbarions) a hyperparameter. k = 0 should not be allowed as it is senseless.
@@ -36,7 +76,44 @@ pub mod wasm;
 // TODO: update routine: benchmark initialization of Gaussian peak on target coordinates.
 // TODO: in general: remove allocating functions by reusing buffers where possible (such as self.prev's)
 
-fn preprocess(image: &GrayImage) -> Vec<f32> {
+// extract the feature channels of a window, and multiply each of them by a cosine window over the feature grid
+fn preprocess(
+    features: &dyn FeatureExtractor,
+    window: &RgbImage,
+    feature_width: u32,
+    feature_height: u32,
+) -> Vec<Vec<f32>> {
+    let mut channels = features.extract(window);
+    for channel in channels.iter_mut() {
+        apply_cosine_window(channel, feature_width, feature_height);
+    }
+    channels
+}
+
+// multiply each value of a row-major feature channel by a cosine window
+fn apply_cosine_window(channel: &mut [f32], width: u32, height: u32) {
+    // note that the values are stored row by row, so we iterate over the rows in the outer loop.
+    // a dimension of a single value is left as is, rather than dividing by zero.
+    let mut position = 0;
+    for j in 0..height {
+        for i in 0..width {
+            let cww = if width > 1 {
+                ((f32::consts::PI * i as f32) / (width - 1) as f32).sin()
+            } else {
+                1.0
+            };
+            let cwh = if height > 1 {
+                ((f32::consts::PI * j as f32) / (height - 1) as f32).sin()
+            } else {
+                1.0
+            };
+            channel[position] *= cww.min(cwh);
+            position += 1;
+        }
+    }
+}
+
+fn log_normalize(image: &GrayImage) -> Vec<f32> {
     let mut prepped: Vec<f32> = image
         .pixels()
         // convert the pixel to u8 and then to f32
@@ -57,130 +134,771 @@ fn preprocess(image: &GrayImage) -> Vec<f32> {
         prepped.iter_mut().for_each(|e| *e /= norm)
     }
 
-    // multiply each pixel by a cosine window
-    let (width, height) = image.dimensions();
-    let mut position = 0;
-    for i in 0..width {
-        for j in 0..height {
-            let cww = ((f32::consts::PI * i as f32) / (width - 1) as f32).sin();
-            let cwh = ((f32::consts::PI * j as f32) / (height - 1) as f32).sin();
-            prepped[position] *= cww.min(cwh);
-            position += 1;
-        }
+    prepped
+}
+
+// A frame the trackers can process: any image with 8-bit pixels, such as a GrayImage, an RgbImage or a DynamicImage.
+// Every window cropped from a frame is converted to RGB, so the feature extractors see the same type of window
+// for grayscale and colour frames. The three colour channels of a window cropped from a grayscale frame are equal.
+pub trait Frame: GenericImageView {
+    // Copy a region of the frame into an RGB image. The region may extend past the edges of the frame,
+    // the pixels outside of it are filled in according to the border mode.
+    fn crop_rgb(
+        &self,
+        left: i64,
+        top: i64,
+        width: u32,
+        height: u32,
+        border_mode: BorderMode,
+    ) -> RgbImage;
+}
+
+impl<I> Frame for I
+where
+    I: GenericImageView,
+    I::Pixel: Pixel<Subpixel = u8>,
+{
+    fn crop_rgb(
+        &self,
+        left: i64,
+        top: i64,
+        width: u32,
+        height: u32,
+        border_mode: BorderMode,
+    ) -> RgbImage {
+        let (frame_width, frame_height) = self.dimensions();
+
+        let fill = match border_mode {
+            BorderMode::Mean => {
+                // the mean colour of the part of the region that lies within the frame, if any
+                let mut sum = [0u64; 3];
+                let mut count = 0u64;
+                let x_range = left.max(0)..(left + width as i64).min(frame_width as i64);
+                let y_range = top.max(0)..(top + height as i64).min(frame_height as i64);
+                for y in y_range {
+                    for x in x_range.clone() {
+                        let pixel = self.get_pixel(x as u32, y as u32).to_rgb();
+                        sum.iter_mut()
+                            .zip(pixel.0)
+                            .for_each(|(s, v)| *s += v as u64);
+                        count += 1;
+                    }
+                }
+                Rgb(sum.map(|s| s.checked_div(count).unwrap_or(0) as u8))
+            }
+            _ => Rgb([0, 0, 0]),
+        };
+
+        RgbImage::from_fn(width, height, |i, j| {
+            let x = border_mode.source_coordinate(left + i as i64, frame_width);
+            let y = border_mode.source_coordinate(top + j as i64, frame_height);
+            match x.zip(y) {
+                Some((x, y)) => self.get_pixel(x, y).to_rgb(),
+                None => fill,
+            }
+        })
     }
+}
 
-    prepped
+// How the pixels of a window that fall outside of the frame are filled in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BorderMode {
+    // black pixels
+    Zero,
+    // the nearest pixel on the edge of the frame
+    #[default]
+    Replicate,
+    // the frame mirrored in its edge, without repeating the edge pixel itself
+    Mirror,
+    // the mean colour of the part of the window inside the frame
+    Mean,
 }
 
-type Identifier = u32;
+impl BorderMode {
+    // The coordinate within a frame dimension of the given length that a (possibly outside) coordinate maps to.
+    // Returns None if the pixel should be filled in with a constant colour.
+    fn source_coordinate(&self, coordinate: i64, length: u32) -> Option<u32> {
+        let length = length as i64;
+        if (0..length).contains(&coordinate) {
+            return Some(coordinate as u32);
+        }
+        if length == 0 {
+            return None;
+        }
+
+        match self {
+            BorderMode::Zero | BorderMode::Mean => None,
+            BorderMode::Replicate => Some(coordinate.clamp(0, length - 1) as u32),
+            BorderMode::Mirror => {
+                if length == 1 {
+                    return Some(0);
+                }
+                // reflecting in both edges repeats with a period of twice the distance between them
+                let period = 2 * (length - 1);
+                let reflected = coordinate.rem_euclid(period);
+                Some(reflected.min(period - reflected) as u32)
+            }
+        }
+    }
+}
 
+// Tracks several targets at once, each with its own tracker. Targets are identified by u32 IDs unless another
+// TargetId type is picked, like `MultiMosseTracker::<u64>::new(settings, desperation_level)`.
 #[derive(Debug)]
-pub struct MultiMosseTracker {
-    // we also store the tracker's numeric ID, and the amount of times it did not make the PSR threshold.
-    trackers: Vec<(Identifier, u32, MosseTracker)>,
+pub struct MultiMosseTracker<Id = u32> {
+    // the active trackers, with their IDs and the amount of times they did not make the PSR threshold
+    trackers: Vec<Target<Id>>,
 
     // the global tracker settings
     settings: MosseTrackerSettings,
 
     // how many times a tracker is allowed to fail the PSR threshold
     desperation_level: u32,
+
+    // expired trackers that may still re-acquire their target, oldest first, and the settings of the pool
+    dormant: Vec<DormantTracker<Id>>,
+    dormant_pool: DormantPoolSettings,
+
+    // the number of frames tracked so far, which timestamps the dormant trackers
+    frame_count: u64,
+
+    // the largest ID used so far, if any, after which add_target() allocates the next one
+    last_id: Option<Id>,
+
+    // drops one of every two targets that overlap, if set
+    duplicate_suppression: Option<DuplicateSuppression>,
+
+    // the number of points kept in the history of every target
+    history_capacity: usize,
+
+    // receives the lifecycle events of the targets, if set
+    event_handler: Option<EventHandler<Id>>,
+}
+
+// Expired trackers are kept in a bounded pool of dormant trackers, instead of being discarded right away.
+// Dormant trackers keep looking for their target on every new frame, and are tried against every target that is
+// added under an allocated ID.
+// A dormant tracker that confidently detects its target becomes active again, under its original identifier.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DormantPoolSettings {
+    // the maximum number of dormant trackers. The oldest are discarded first.
+    pub capacity: usize,
+    // the number of frames after which a dormant tracker is discarded, if set
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Target<Id> {
+    id: Id,
+    // the number of consecutive frames on which the tracker did not make the PSR threshold
+    death_watch: u32,
+    // paused trackers are neither run nor updated, and never expire
+    paused: bool,
+    // the number of consecutive detection frames on which the target was not matched with a detection
+    misses: u32,
+    tracker: MosseTracker,
+    // the last points of the target, oldest first
+    history: VecDeque<TrackPoint>,
+}
+
+// A snapshot of an active target, as returned by MultiMosseTracker::targets() and get_target().
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetInfo<Id = u32> {
+    pub id: Id,
+    // the last known position of the center of the target
+    pub location: (f32, f32),
+    pub psr: f32,
+    pub scale: f32,
+    // the size of the tracking window the target was trained on, in pixels
+    pub window_size: (u32, u32),
+    // the number of consecutive frames on which the PSR did not exceed the threshold
+    pub missed_frames: u32,
+    pub paused: bool,
+}
+
+impl<Id: TargetId> Target<Id> {
+    fn info(&self) -> TargetInfo<Id> {
+        TargetInfo {
+            id: self.id,
+            location: self.tracker.current_target_center,
+            psr: self.tracker.last_psr,
+            scale: self.tracker.current_scale,
+            window_size: (self.tracker.window_width, self.tracker.window_height),
+            missed_frames: self.death_watch,
+            paused: self.paused,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DormantTracker<Id> {
+    id: Id,
+    tracker: MosseTracker,
+    // the last known position of the target, and the frame on which the tracker expired
+    last_location: (f32, f32),
+    since: u64,
+    history: VecDeque<TrackPoint>,
 }
 
-impl MultiMosseTracker {
-    pub fn new(settings: MosseTrackerSettings, desperation_level: u32) -> MultiMosseTracker {
+impl<Id: TargetId> MultiMosseTracker<Id> {
+    pub fn new(settings: MosseTrackerSettings, desperation_level: u32) -> MultiMosseTracker<Id> {
+        MultiMosseTracker::with_dormant_pool(
+            settings,
+            desperation_level,
+            DormantPoolSettings {
+                capacity: 0,
+                max_age: None,
+            },
+        )
+    }
+
+    pub fn with_dormant_pool(
+        settings: MosseTrackerSettings,
+        desperation_level: u32,
+        dormant_pool: DormantPoolSettings,
+    ) -> MultiMosseTracker<Id> {
         MultiMosseTracker {
             trackers: Vec::new(),
             settings,
             desperation_level,
+            dormant: Vec::new(),
+            dormant_pool,
+            frame_count: 0,
+            last_id: None,
+            duplicate_suppression: None,
+            history_capacity: 0,
+            event_handler: None,
         }
     }
 
-    pub fn add_or_replace_target(&mut self, id: Identifier, coords: (u32, u32), frame: &GrayImage) {
-        // Add a target by specifying its coords and a new ID.
-        // Specify an existing ID to replace an existing tracked target.
+    // Register a handler for the lifecycle events of the targets, replacing the previous one.
+    pub fn set_event_handler<H: FnMut(&TrackerEvent<Id>) + Send + 'static>(&mut self, handler: H) {
+        self.event_handler = Some(EventHandler(Box::new(handler)));
+    }
 
-        // create a new tracker for this target and train it
-        let mut new_tracker = MosseTracker::new(&self.settings);
-        new_tracker.train(frame, coords);
+    pub fn clear_event_handler(&mut self) {
+        self.event_handler = None;
+    }
 
-        match self.trackers.iter_mut().find(|tracker| tracker.0 == id) {
-            Some(tuple) => {
-                tuple.1 = 0;
-                tuple.2 = new_tracker;
+    fn emit(&mut self, event: TrackerEvent<Id>) {
+        if let Some(EventHandler(handler)) = &mut self.event_handler {
+            handler(&event);
+        }
+    }
+
+    // Add a target by specifying its coords and a new ID.
+    // Specify an existing ID to replace an existing tracked target, or the ID of a dormant tracker to replace it.
+    // The target is always tracked under the given ID, which is returned: dormant trackers only take over the
+    // targets added with add_target(), whose IDs are up to the multi-tracker.
+    pub fn add_or_replace_target<F: Frame>(
+        &mut self,
+        id: Id,
+        coords: (f32, f32),
+        frame: &F,
+    ) -> Result<Id, MosseError> {
+        self.insert_target(id, coords, frame, None)
+    }
+
+    // Add or replace a target like add_or_replace_target(), but track it with its own settings instead of the
+    // global ones, for example with a window that fits the size of the target. The settings must have the frame
+    // dimensions of the global settings; settings() is a good starting point to override some of them.
+    pub fn add_or_replace_target_with_settings<F: Frame>(
+        &mut self,
+        id: Id,
+        coords: (f32, f32),
+        frame: &F,
+        settings: &MosseTrackerSettings,
+    ) -> Result<Id, MosseError> {
+        self.insert_target(id, coords, frame, Some(settings))
+    }
+
+    // Add or replace a target, with the global settings unless it has its own
+    fn insert_target<F: Frame>(
+        &mut self,
+        id: Id,
+        coords: (f32, f32),
+        frame: &F,
+        own_settings: Option<&MosseTrackerSettings>,
+    ) -> Result<Id, MosseError> {
+        self.check_new_target(frame, own_settings)?;
+
+        // the ID will never be allocated
+        self.last_id = self.last_id.max(Some(id));
+
+        // the ID now belongs to this target
+        let dormant = self.dormant.len();
+        self.dormant.retain(|dormant| dormant.id != id);
+        let mut replaced = self.dormant.len() != dormant;
+
+        // create a new tracker for this target and train it
+        let mut new_tracker = MosseTracker::new(own_settings.unwrap_or(&self.settings))?;
+        new_tracker.train(frame, coords)?;
+
+        match self.trackers.iter_mut().find(|target| target.id == id) {
+            Some(target) => {
+                target.death_watch = 0;
+                target.paused = false;
+                target.misses = 0;
+                target.tracker = new_tracker;
+                target.history.clear();
+                replaced = true;
             }
             // add the tracker to the map
-            _ => self.trackers.push((id, 0, new_tracker)),
+            _ => self.trackers.push(Target {
+                id,
+                death_watch: 0,
+                paused: false,
+                misses: 0,
+                tracker: new_tracker,
+                history: VecDeque::new(),
+            }),
         };
+        if replaced {
+            self.emit_replaced(id, coords);
+        }
+        self.record_location(id, coords);
+        Ok(id)
+    }
+
+    // check that a new target is for frames of the right size, and so are its own settings
+    fn check_new_target<F: Frame>(
+        &self,
+        frame: &F,
+        own_settings: Option<&MosseTrackerSettings>,
+    ) -> Result<(), MosseError> {
+        check_frame_size(frame, self.settings.width, self.settings.height)?;
+        match own_settings {
+            Some(settings)
+                if (settings.width, settings.height)
+                    != (self.settings.width, self.settings.height) =>
+            {
+                Err(MosseError::InvalidSetting {
+                    name: "frame size",
+                    reason: "must match the frame size of the multi-tracker",
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Let the first dormant tracker that recognizes a new target take it over, under its original ID, which is
+    // returned. A target with its own settings keeps the ID, but is tracked by a new tracker with those settings.
+    fn revive_dormant<F: Frame>(
+        &mut self,
+        coords: (f32, f32),
+        frame: &F,
+        own_settings: Option<&MosseTrackerSettings>,
+    ) -> Result<Option<Id>, MosseError> {
+        self.check_new_target(frame, own_settings)?;
+        for index in 0..self.dormant.len() {
+            if !self.dormant[index].tracker.recognizes(frame, coords)? {
+                continue;
+            }
+            let new_tracker = match own_settings {
+                Some(settings) => {
+                    let mut tracker = MosseTracker::new(settings)?;
+                    tracker.train(frame, coords)?;
+                    Some(tracker)
+                }
+                None => None,
+            };
+            let DormantTracker {
+                id,
+                mut tracker,
+                history,
+                ..
+            } = self.dormant.remove(index);
+            match new_tracker {
+                Some(new_tracker) => tracker = new_tracker,
+                None => {
+                    tracker.resume(coords);
+                    tracker.update(frame);
+                }
+            }
+            self.trackers.push(Target {
+                id,
+                death_watch: 0,
+                paused: false,
+                misses: 0,
+                tracker,
+                history,
+            });
+            self.record_location(id, coords);
+            self.emit(TrackerEvent::TargetRecovered {
+                id,
+                frame: self.frame_count,
+                location: coords,
+            });
+            return Ok(Some(id));
+        }
+        Ok(None)
+    }
+
+    fn emit_replaced(&mut self, id: Id, location: (f32, f32)) {
+        self.emit(TrackerEvent::TargetReplaced {
+            id,
+            frame: self.frame_count,
+            location,
+        });
     }
 
-    pub fn track(&mut self, frame: &GrayImage) -> Vec<(Identifier, Prediction)> {
-        let mut predictions: Vec<(Identifier, Prediction)> = Vec::new();
-        for (id, death_watch, tracker) in &mut self.trackers {
+    pub fn track<F: Frame>(&mut self, frame: &F) -> Result<Vec<(Id, Prediction)>, MosseError> {
+        check_frame_size(frame, self.settings.width, self.settings.height)?;
+        self.frame_count += 1;
+
+        let frame_count = self.frame_count;
+        let mut predictions: Vec<(Id, Prediction)> = Vec::new();
+        let mut events = Vec::new();
+        for target in self.trackers.iter_mut().filter(|target| !target.paused) {
             // compute the location of the object in the new frame and save it
-            let pred = tracker.track_new_frame(frame);
-            predictions.push((*id, pred));
+            let tracker = &mut target.tracker;
+            let pred = tracker.track_replaceably(frame, frame_count)?;
+            let location = pred.location;
+            predictions.push((target.id, pred));
 
             // if the tracker made the PSR threshold, update it.
-            // if not, we increment its death ticker.
-            if tracker.last_psr > self.settings.psr_threshold {
-                tracker.update(frame);
-                *death_watch = 0u32;
+            // if not, or if its target left the frame, we increment its death ticker.
+            if tracker.last_psr > tracker.psr_threshold && !tracker.has_left_frame() {
+                tracker.update_replaceably(frame, frame_count);
+                if target.death_watch > 0 {
+                    events.push(TrackerEvent::TargetRecovered {
+                        id: target.id,
+                        frame: frame_count,
+                        location,
+                    });
+                }
+                target.death_watch = 0u32;
             } else {
-                *death_watch += 1;
+                if target.death_watch == 0 {
+                    events.push(TrackerEvent::TargetLost {
+                        id: target.id,
+                        frame: frame_count,
+                        location,
+                    });
+                }
+                target.death_watch += 1;
             }
         }
 
-        // prune all filters with an expired death ticker
-        let level = &self.desperation_level;
-        self.trackers
-            .retain(|(_id, death_count, _tracker)| death_count < level);
+        // dormant trackers that find their target again become active
+        let mut index = 0;
+        while index < self.dormant.len() {
+            match self.dormant[index].tracker.reacquire(frame)? {
+                Some(pred) => {
+                    let DormantTracker {
+                        id,
+                        mut tracker,
+                        history,
+                        ..
+                    } = self.dormant.remove(index);
+                    tracker.update_replaceably(frame, frame_count);
+                    events.push(TrackerEvent::TargetRecovered {
+                        id,
+                        frame: frame_count,
+                        location: pred.location,
+                    });
+                    predictions.push((id, pred));
+                    self.trackers.push(Target {
+                        id,
+                        death_watch: 0,
+                        paused: false,
+                        misses: 0,
+                        tracker,
+                        history,
+                    });
+                }
+                None => index += 1,
+            }
+        }
+
+        self.suppress_duplicates(&mut predictions, &mut events);
+        self.record_predictions(&predictions);
+
+        // move all trackers with an expired death ticker to the dormant pool
+        let level = self.desperation_level;
+        let (active, expired) = self
+            .trackers
+            .drain(..)
+            .partition(|target: &Target<Id>| target.death_watch < level);
+        self.trackers = active;
+        for Target {
+            id,
+            tracker,
+            history,
+            ..
+        } in expired
+        {
+            if self.dormant_pool.capacity == 0 {
+                events.push(TrackerEvent::TargetRemoved {
+                    id,
+                    frame: frame_count,
+                    reason: RemovalReason::Expired,
+                });
+                continue;
+            }
+            self.dormant.push(DormantTracker {
+                id,
+                last_location: tracker.current_target_center,
+                tracker,
+                since: frame_count,
+                history,
+            });
+        }
+
+        // discard the oldest dormant trackers
+        if let Some(max_age) = self.dormant_pool.max_age {
+            let (too_old, dormant) = self
+                .dormant
+                .drain(..)
+                .partition(|dormant: &DormantTracker<Id>| frame_count - dormant.since > max_age);
+            self.dormant = dormant;
+            events.extend(
+                too_old
+                    .into_iter()
+                    .map(|dormant| TrackerEvent::TargetRemoved {
+                        id: dormant.id,
+                        frame: frame_count,
+                        reason: RemovalReason::Expired,
+                    }),
+            );
+        }
+        let excess = self
+            .dormant
+            .len()
+            .saturating_sub(self.dormant_pool.capacity);
+        let evicted: Vec<DormantTracker<Id>> = self.dormant.drain(..excess).collect();
+        events.extend(
+            evicted
+                .into_iter()
+                .map(|dormant| TrackerEvent::TargetRemoved {
+                    id: dormant.id,
+                    frame: frame_count,
+                    reason: RemovalReason::Evicted,
+                }),
+        );
 
-        predictions
+        for event in events {
+            self.emit(event);
+        }
+        Ok(predictions)
     }
 
-    pub fn dump_filter_reals(&self) -> Vec<GrayImage> {
-        return self.trackers.iter().map(|t| t.2.dump_filter().0).collect();
+    pub fn dump_filter_reals(&self) -> Result<Vec<GrayImage>, MosseError> {
+        self.trackers
+            .iter()
+            .map(|t| t.tracker.dump_filter())
+            .collect()
     }
 
     pub fn size(&self) -> usize {
         self.trackers.len()
     }
+
+    // the global settings, with which targets are tracked unless they were added with their own settings
+    pub fn settings(&self) -> &MosseTrackerSettings {
+        &self.settings
+    }
+
+    // Stop tracking a target, whether it is active or dormant.
+    pub fn remove_target(&mut self, id: Id) -> Result<(), MosseError> {
+        let (active, dormant) = (self.trackers.len(), self.dormant.len());
+        self.trackers.retain(|target| target.id != id);
+        self.dormant.retain(|dormant| dormant.id != id);
+        if self.trackers.len() == active && self.dormant.len() == dormant {
+            return Err(unknown_target(id));
+        }
+        self.emit(TrackerEvent::TargetRemoved {
+            id,
+            frame: self.frame_count,
+            reason: RemovalReason::Removed,
+        });
+        Ok(())
+    }
+
+    // Freeze an active target: until it is resumed, it is left out of the predictions, its tracker is not updated
+    // and it does not expire.
+    pub fn pause_target(&mut self, id: Id) -> Result<(), MosseError> {
+        self.active_target(id)?.paused = true;
+        Ok(())
+    }
+
+    // Resume tracking a paused target from its last known position, with a clean slate: no missed frames, so it is
+    // searched for in the usual detection window, and a motion model that starts over at rest, as the velocity it
+    // had before the pause is stale.
+    pub fn resume_target(&mut self, id: Id) -> Result<(), MosseError> {
+        let target = self.active_target(id)?;
+        target.paused = false;
+        target.death_watch = 0;
+        let location = target.tracker.current_target_center;
+        target.tracker.resume(location);
+        Ok(())
+    }
+
+    // The active targets, including the paused ones, in the order they were added.
+    pub fn targets(&self) -> impl Iterator<Item = TargetInfo<Id>> + '_ {
+        self.trackers.iter().map(Target::info)
+    }
+
+    pub fn get_target(&self, id: Id) -> Option<TargetInfo<Id>> {
+        self.trackers
+            .iter()
+            .find(|target| target.id == id)
+            .map(Target::info)
+    }
+
+    fn active_target(&mut self, id: Id) -> Result<&mut Target<Id>, MosseError> {
+        self.trackers
+            .iter_mut()
+            .find(|target| target.id == id)
+            .ok_or_else(|| unknown_target(id))
+    }
+
+    // The IDs of the dormant trackers, oldest first, with the last known positions of their targets and the
+    // number of the frame on which they expired. Frames are numbered from 1 by every call to track().
+    pub fn dormant_targets(&self) -> Vec<(Id, (f32, f32), u64)> {
+        self.dormant
+            .iter()
+            .map(|dormant| (dormant.id, dormant.last_location, dormant.since))
+            .collect()
+    }
+}
+
+// IDs can be allocated by the multi-tracker for ID types that follow each other, like integers
+impl<Id: SequentialId> MultiMosseTracker<Id> {
+    // Add a target under a newly allocated ID, which is returned. IDs are never allocated twice, and never collide
+    // with IDs picked by the caller. A new target that is recognized by a dormant tracker is tracked by that
+    // tracker instead, under its original ID, and no ID is allocated.
+    pub fn add_target<F: Frame>(
+        &mut self,
+        coords: (f32, f32),
+        frame: &F,
+    ) -> Result<Id, MosseError> {
+        if let Some(id) = self.revive_dormant(coords, frame, None)? {
+            return Ok(id);
+        }
+        let id = self.allocate_id()?;
+        self.insert_target(id, coords, frame, None)
+    }
+
+    // Add a target under a newly allocated ID, and track it with its own settings, like
+    // add_or_replace_target_with_settings(). A new target that is recognized by a dormant tracker gets the original
+    // ID of that tracker, but the dormant tracker is discarded, and the target is tracked with the given settings.
+    pub fn add_target_with_settings<F: Frame>(
+        &mut self,
+        coords: (f32, f32),
+        frame: &F,
+        settings: &MosseTrackerSettings,
+    ) -> Result<Id, MosseError> {
+        if let Some(id) = self.revive_dormant(coords, frame, Some(settings))? {
+            return Ok(id);
+        }
+        let id = self.allocate_id()?;
+        self.insert_target(id, coords, frame, Some(settings))
+    }
+
+    fn allocate_id(&self) -> Result<Id, MosseError> {
+        match self.last_id {
+            Some(last_id) => last_id.next().ok_or(MosseError::IdentifiersExhausted),
+            None => Ok(Id::first()),
+        }
+    }
+}
+
+fn unknown_target<Id: TargetId>(id: Id) -> MosseError {
+    MosseError::UnknownTarget(format!("{:?}", id))
 }
 
 pub struct Prediction {
-    pub location: (u32, u32),
+    pub location: (f32, f32),
     pub psr: f32,
+    // size of the target relative to the size of the tracking window it was trained on.
+    // Always 1.0 if scale estimation is disabled.
+    pub scale: f32,
+    // whether the center of the target has moved outside of the frame
+    pub left_frame: bool,
+    // the state of the motion model, if enabled
+    pub motion: Option<MotionEstimate>,
+    // the number of consecutive frames, up to and including this one, on which the PSR did not exceed the threshold
+    pub missed_frames: u32,
 }
 
 pub struct MosseTracker {
-    filter: Vec<Complex<f32>>,
+    // one filter per feature channel
+    filter: Vec<Vec<Complex<f32>>>,
 
     // constants frame height
     frame_width: u32,
     frame_height: u32,
 
     // stores dimensions of tracking window and its center
-    // window is square for now, this variable contains the size of the square edge
-    window_size: u32,
-    current_target_center: (u32, u32), // represents center in frame
+    window_width: u32,
+    window_height: u32,
+    current_target_center: (f32, f32), // represents center in frame, with sub-pixel precision
+
+    // how the parts of windows outside of the frame are filled in
+    border_mode: BorderMode,
 
-    // the 'target' (G). A single Gaussian peak centered at the tracking window.
+    // extracts the feature channels from a window, and the dimensions of the resulting feature grid
+    features: Arc<dyn FeatureExtractor>,
+    feature_width: u32,
+    feature_height: u32,
+
+    // dimensions of the detection window, which may be larger than the tracking window, and of its feature grid
+    search_width: u32,
+    search_height: u32,
+    search_feature_width: u32,
+    search_feature_height: u32,
+
+    // replaces the linear filter with a kernelized correlation filter (KCF) if set
+    kernel_filter: Option<KernelizedFilter>,
+
+    // the optional motion model that predicts where to look for the target on the next frame
+    motion_model: Option<MotionModel>,
+    kalman_filter: Option<KalmanFilter>,
+
+    // searches a growing region for a target that is missed if set, and the number of consecutive misses
+    redetection: Option<RedetectionSettings>,
+    missed_frames: u32,
+
+    // the current size of the target relative to the tracking window, and the optional filter that estimates it
+    current_scale: f32,
+    scale_filter: Option<ScaleFilter>,
+
+    // the 'target' (G). A single Gaussian peak centered at the feature grid.
     target: Vec<Complex<f32>>,
 
-    // constants: learning rate and PSR threshold
+    // constants: learning rate and PSR threshold, and the size of the area around the peak excluded from the PSR
     eta: f32,
+    psr_threshold: f32,
+    psr_exclusion_size: u32,
     regularization: f32, // not super important for MOSSE: see paper fig 4.
 
-    // the previous Ai and Bi
-    last_top: Vec<Complex<f32>>,
+    // the perturbations of the first window the linear filter is trained on
+    augmentation: Augmentation,
+
+    // the previous Ai (one per feature channel) and Bi (shared by all channels)
+    last_top: Vec<Vec<Complex<f32>>>,
     last_bottom: Vec<Complex<f32>>,
 
     // the previous psr
     pub last_psr: f32,
 
-    // thread-safe FFT objects containing precomputed parameters for this input data size.
-    fft: Arc<dyn Fft<f32>>,
-    inv_fft: Arc<dyn Fft<f32>>,
+    // the state of the tracker before the last frame of a multi-tracker, so that a location corrected by a detector
+    // can replace what the tracker found and learned on that frame, instead of adding to it
+    replaceable_frame: Option<ReplaceableFrame>,
+
+    // the settings the tracker was created with, which are saved with its state
+    settings: MosseTrackerSettings,
+
+    // thread-safe 2D FFT objects containing precomputed parameters for the window size and the detection window size.
+    fft: Arc<Fft2d>,
+    search_fft: Arc<Fft2d>,
 }
 
 impl Debug for MosseTracker {
@@ -189,385 +907,945 @@ impl Debug for MosseTracker {
             .field("filter", &self.filter)
             .field("frame_width", &self.frame_width)
             .field("frame_height", &self.frame_height)
-            .field("window_size", &self.window_size)
+            .field("window_width", &self.window_width)
+            .field("window_height", &self.window_height)
             .field("current_target_center", &self.current_target_center)
+            .field("border_mode", &self.border_mode)
+            .field("features", &self.features)
+            .field("feature_width", &self.feature_width)
+            .field("feature_height", &self.feature_height)
+            .field("search_width", &self.search_width)
+            .field("search_height", &self.search_height)
+            .field("search_feature_width", &self.search_feature_width)
+            .field("search_feature_height", &self.search_feature_height)
+            .field("kernel_filter", &self.kernel_filter)
+            .field("motion_model", &self.motion_model)
+            .field("kalman_filter", &self.kalman_filter)
+            .field("redetection", &self.redetection)
+            .field("missed_frames", &self.missed_frames)
+            .field("current_scale", &self.current_scale)
+            .field("scale_filter", &self.scale_filter)
             .field("target", &self.target)
             .field("eta", &self.eta)
+            .field("psr_threshold", &self.psr_threshold)
+            .field("psr_exclusion_size", &self.psr_exclusion_size)
             .field("regularization", &self.regularization)
+            .field("augmentation", &self.augmentation)
             .field("last_top", &self.last_top)
             .field("last_bottom", &self.last_bottom)
             .field("last_psr", &self.last_psr)
+            .field("replaceable_frame", &self.replaceable_frame)
+            .field("settings", &self.settings)
             // These fields don't implement Debug, so I can't use the #[derive(Debug)] impl.
             // .field("fft", &self.fft)
-            // .field("inv_fft", &self.inv_fft)
+            // .field("search_fft", &self.search_fft)
             .finish()
     }
 }
 
-#[derive(Debug)]
+// everything a tracker learns when it is updated
+#[derive(Debug, Clone)]
+struct LearnedModel {
+    filter: Vec<Vec<Complex<f32>>>,
+    last_top: Vec<Vec<Complex<f32>>>,
+    last_bottom: Vec<Complex<f32>>,
+    kernel_filter: Option<KernelizedFilter>,
+    scale_filter: Option<ScaleFilter>,
+}
+
+#[derive(Debug, Clone)]
+struct ReplaceableFrame {
+    // the number of the frame
+    frame_number: u64,
+    // the Kalman filter before it predicted and measured the target on the frame. None if the filter was started on
+    // the frame, by a dormant tracker that found its target.
+    kalman_filter: Option<KalmanFilter>,
+    // the learned model before the update on the frame, if the tracker was updated
+    model: Option<LearnedModel>,
+}
+
+// With the serde feature, settings are (de)serialized through the builder. See the config module.
+#[derive(Debug, Clone)]
 pub struct MosseTrackerSettings {
     pub width: u32,
     pub height: u32,
-    pub window_size: u32,
+    pub window_width: u32,
+    pub window_height: u32,
     pub learning_rate: f32,
     pub psr_threshold: f32,
     pub regularization: f32,
+    // enables estimation of the target scale with a separate scale filter (DSST) if set
+    pub scale_estimation: Option<ScaleEstimationSettings>,
+    // use a kernelized correlation filter (KCF) with this kernel instead of the linear MOSSE filter if set
+    pub kernel: Option<Kernel>,
+    // extracts the feature channels the filter is trained on, like raw pixel intensities or HOG
+    pub features: Arc<dyn FeatureExtractor>,
+    // The filter is trained on the tracking window, but detection runs over a window that is this many times as
+    // wide and high, centered on the target. Values larger than 1.0 allow the target to move further between frames.
+    pub search_area_factor: f32,
+    // how the parts of windows that fall outside of the frame are filled in
+    pub border_mode: BorderMode,
+    // predicts the position of the target on the next frame with a Kalman filter if set
+    pub motion_model: Option<MotionModel>,
+    // searches a growing region for a target whose PSR does not exceed the threshold if set
+    pub redetection: Option<RedetectionSettings>,
+    // the standard deviation of the Gaussian peak the filter is trained to respond with, in cells of the feature grid
+    pub target_sigma: f32,
+    // the width and height of the square around the correlation peak that is left out of the sidelobe when computing
+    // the PSR, in cells of the feature grid
+    pub psr_exclusion_size: u32,
+    // the perturbed copies of the first window the linear filter is trained on
+    pub augmentation: Augmentation,
+}
+
+// The perturbations applied to the first window, to train the linear filter on a set of slightly different copies
+// of the target as described in the MOSSE paper. The kernelized filter is trained on the unperturbed window only.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct Augmentation {
+    // the angles of the rotated copies, in radians (clockwise)
+    pub rotations: Vec<f32>,
+    // the scale factors of the zoomed copies
+    pub scales: Vec<f32>,
+}
+
+impl Default for Augmentation {
+    fn default() -> Self {
+        Augmentation {
+            rotations: vec![
+                0.02, -0.02, 0.05, -0.05, 0.07, -0.07, 0.09, -0.09, 1.1, -1.1, 1.3, -1.3, 1.5,
+                -1.5, 2.0, -2.0,
+            ],
+            scales: vec![0.8, 0.9, 1.1, 1.2],
+        }
+    }
+}
+
+// Re-detection of missed targets.
+// While the PSR of a target stays below the threshold, the filter is no longer updated and the target is searched
+// for in a region around its last known position that grows on every frame, until it is found again.
+// Note that a MultiMosseTracker still gives up on a target after desperation_level missed frames.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct RedetectionSettings {
+    // the factor by which the width and height of the detection window grow on every missed frame
+    pub growth_factor: f32,
+    // search the whole frame once the target has been missed on this many consecutive frames, if set
+    pub full_frame_after: Option<u32>,
+}
+
+impl Default for RedetectionSettings {
+    fn default() -> Self {
+        RedetectionSettings {
+            growth_factor: 1.5,
+            full_frame_after: Some(5),
+        }
+    }
 }
 
 #[allow(non_snake_case)]
 impl MosseTracker {
-    pub fn new(settings: &MosseTrackerSettings) -> MosseTracker {
-        // parameterize the FFT objects
-        let mut planner = FftPlanner::new();
-        let mut inv_planner = FftPlanner::new();
+    pub fn new(settings: &MosseTrackerSettings) -> Result<MosseTracker, MosseError> {
+        if settings.width == 0 || settings.height == 0 {
+            return Err(MosseError::InvalidFrameSize {
+                width: settings.width,
+                height: settings.height,
+            });
+        }
+        if settings.window_width == 0 || settings.window_height == 0 {
+            return Err(MosseError::InvalidWindowSize {
+                width: settings.window_width,
+                height: settings.window_height,
+            });
+        }
 
-        // NOTE: we initialize the FFTs based on the size of the window
-        let length = (settings.window_size * settings.window_size) as usize;
-        let fft = planner.plan_fft_forward(length);
-        let inv_fft = inv_planner.plan_fft_inverse(length);
+        // the features of the window are computed on a grid of cells, one cell per pixel for raw intensities
+        let cell_size = settings.features.cell_size().max(1);
+        let feature_width = (settings.window_width / cell_size).max(1);
+        let feature_height = (settings.window_height / cell_size).max(1);
+        let channels = settings.features.channels();
+
+        // NOTE: we initialize the FFTs based on the size of the feature grid
+        // NOTE: the filter and its parts only hold the non-redundant half of the spectrum of real input
+        let fft = Fft2d::new(feature_width, feature_height);
+        let length = fft.spectrum_len();
+
+        // the detection window is at least as large as the tracking window, but no larger than the frame
+        let search_width = ((settings.window_width as f32 * settings.search_area_factor).round()
+            as u32)
+            .min(settings.width)
+            .max(settings.window_width);
+        let search_height = ((settings.window_height as f32 * settings.search_area_factor).round()
+            as u32)
+            .min(settings.height)
+            .max(settings.window_height);
+        let search_feature_width = (search_width / cell_size).max(feature_width);
+        let search_feature_height = (search_height / cell_size).max(feature_height);
+        let search_fft = Fft2d::new(search_feature_width, search_feature_height);
 
         // initialize the filter and its top and bottom parts with zeroes.
-        let filter = vec![Complex::zero(); length];
-        let top = vec![Complex::zero(); length];
+        let filter = vec![vec![Complex::zero(); length]; channels];
+        let top = vec![vec![Complex::zero(); length]; channels];
         let bottom = vec![Complex::zero(); length];
 
         // initialize the target output map (G), with a compact Gaussian peak centered on the target object.
         // In the Bolme paper, this map is called gi.
-        let mut target: Vec<Complex<f32>> =
-            build_target(settings.window_size, settings.window_size)
-                .into_iter()
-                .map(|p| Complex::new(p, 0.0))
-                .collect();
-        fft.process(&mut target);
+        let target = fft.forward(&mut build_target(
+            feature_width,
+            feature_height,
+            settings.target_sigma,
+        ));
+
+        let scale_filter = settings.scale_estimation.map(|scale_settings| {
+            ScaleFilter::new(
+                &scale_settings,
+                settings.window_width,
+                settings.window_height,
+                settings.width,
+                settings.height,
+                settings.border_mode,
+            )
+        });
+
+        let kernel_filter = settings.kernel.map(|kernel| {
+            KernelizedFilter::new(
+                kernel,
+                settings.learning_rate,
+                settings.regularization,
+                length,
+            )
+        });
 
-        MosseTracker {
+        Ok(MosseTracker {
             filter,
             last_top: top,
             last_bottom: bottom,
             last_psr: 0.0,
             eta: settings.learning_rate,
+            psr_threshold: settings.psr_threshold,
+            psr_exclusion_size: settings.psr_exclusion_size,
             regularization: settings.regularization,
+            augmentation: settings.augmentation.clone(),
+            settings: settings.clone(),
             target,
             fft,
-            inv_fft,
+            search_fft,
             frame_width: settings.width,
             frame_height: settings.height,
-            window_size: settings.window_size,
-            current_target_center: (0, 0),
-        }
+            window_width: settings.window_width,
+            window_height: settings.window_height,
+            current_target_center: (0.0, 0.0),
+            border_mode: settings.border_mode,
+            features: settings.features.clone(),
+            feature_width,
+            feature_height,
+            search_width,
+            search_height,
+            search_feature_width,
+            search_feature_height,
+            kernel_filter,
+            motion_model: settings.motion_model,
+            kalman_filter: None,
+            redetection: settings.redetection,
+            missed_frames: 0,
+            current_scale: 1.0,
+            scale_filter,
+            replaceable_frame: None,
+        })
     }
 
-    fn compute_2dfft(&self, imagedata: Vec<f32>) -> Vec<Complex<f32>> {
-        let mut buffer: Vec<Complex<f32>> = imagedata
-            .into_iter()
-            .map(|p| Complex::new(p, 0.0))
-            .collect();
-
-        // fft.process() CONSUMES the input buffer as scratch space, make sure it is not reused
-        self.fft.process(&mut buffer);
-
-        buffer
+    fn compute_2dfft(&self, mut imagedata: Vec<f32>) -> Vec<Complex<f32>> {
+        // the image data is real, so we only compute half of its (Hermitian-symmetric) spectrum
+        self.fft.forward(&mut imagedata)
     }
 
     // Train a new filter on the first frame in which the object occurs
-    pub fn train(&mut self, input_frame: &GrayImage, target_center: (u32, u32)) {
-        // store the target center as the current
+    pub fn train<F: Frame>(
+        &mut self,
+        input_frame: &F,
+        target_center: (f32, f32),
+    ) -> Result<(), MosseError> {
+        self.check_frame(input_frame)?;
+
+        // store the target center as the current, and reset the scale to that of the tracking window
         self.current_target_center = target_center;
+        self.current_scale = 1.0;
+
+        self.missed_frames = 0;
+
+        // (re)start the motion model at the target center, at rest
+        self.kalman_filter = self
+            .motion_model
+            .map(|model| KalmanFilter::new(model, target_center));
 
         // cut out the training template by cropping
         let window = &window_crop(
             input_frame,
-            self.window_size,
-            self.window_size,
+            self.window_width,
+            self.window_height,
             target_center,
+            self.border_mode,
         );
 
-        #[cfg(debug_assertions)]
-        {
-            window.save("WINDOW.png").unwrap();
+        if let Some(kernel_filter) = &mut self.kernel_filter {
+            // the kernelized filter is trained on the unperturbed window only, as in the KCF paper
+            let features = preprocess(
+                &*self.features,
+                window,
+                self.feature_width,
+                self.feature_height,
+            );
+            kernel_filter.train(&self.fft, features, &self.target);
+        } else {
+            self.train_linear_filter(window);
         }
 
-        // build an iterator that produces training frames that have been slightly rotated according to a theta value.
-        let rotated_frames = [
-            0.02, -0.02, 0.05, -0.05, 0.07, -0.07, 0.09, -0.09, 1.1, -1.1, 1.3, -1.3, 1.5, -1.5,
-            2.0, -2.0,
-        ]
-        .iter()
-        .map(|rad| {
-            // Rotate an image clockwise about its center by theta radians.
-            let training_frame =
-                rotate_about_center(window, *rad, Interpolation::Nearest, Luma([0]));
+        // train the scale filter on the same frame
+        if let Some(scale_filter) = &mut self.scale_filter {
+            scale_filter.train(
+                input_frame,
+                self.current_target_center,
+                self.window_width,
+                self.window_height,
+                self.current_scale,
+            );
+        }
 
-            #[cfg(debug_assertions)]
-            {
-                training_frame
-                    .save(format!("training_frame_rotated_theta_{}.png", rad))
-                    .unwrap();
-            }
+        Ok(())
+    }
 
-            training_frame
-        });
+    pub fn track_new_frame<F: Frame>(&mut self, frame: &F) -> Result<Prediction, MosseError> {
+        self.check_frame(frame)?;
 
-        // build an iterator that produces training frames that have been slightly scaled to various degrees ('zoomed')
-        let scaled_frames = [0.8, 0.9, 1.1, 1.2].into_iter().map(|scalefactor| {
-            let scale = Projection::scale(scalefactor, scalefactor);
+        // look for the target where the motion model expects it, if any
+        if let Some(kalman_filter) = &mut self.kalman_filter {
+            self.current_target_center = kalman_filter.predict();
+        }
 
-            let scaled_training_frame = warp(window, &scale, Interpolation::Nearest, Luma([0]));
+        // the detection window grows while the target is being searched for
+        let (search_width, search_height, search_center) = self.search_region();
+        let ((new_x, new_y), psr) =
+            self.locate(frame, search_width, search_height, search_center)?;
+        self.last_psr = psr;
+        let confident = self.last_psr > self.psr_threshold;
+        if confident {
+            self.missed_frames = 0;
+        } else {
+            self.missed_frames += 1;
+        }
 
-            #[cfg(debug_assertions)]
-            {
-                scaled_training_frame
-                    .save(format!("training_frame_scaled_{}.png", scalefactor))
-                    .unwrap();
+        // A confident detection corrects the motion model. Otherwise, the target is assumed to be where the
+        // motion model predicts it, for instance while it is occluded. Without a motion model, a target that is
+        // searched for is assumed to stay at its last known position.
+        match &mut self.kalman_filter {
+            Some(kalman_filter) if !confident => {
+                self.current_target_center = kalman_filter.position();
             }
-
-            scaled_training_frame
-        });
-
-        // Chain these iterators together.
-        // Note that we add the initial, unperturbed training frame as first in line.
-        let training_frames = std::iter::once(window)
-            .cloned()
-            .chain(rotated_frames)
-            .chain(scaled_frames);
-        // TODO: scaling is not ready yet
-        // .chain(scaled_frames);
-
-        let mut training_frame_count = 0;
-        for training_frame in training_frames {
-            // preprocess the training frame using preprocess()
-            let vectorized = preprocess(&training_frame);
-
-            // calculate the 2D FFT of the preprocessed frame: FFT(fi) = Fi
-            let Fi = self.compute_2dfft(vectorized);
-
-            //  compute the complex conjugate of Fi, Fi*.
-            let Fi_star: Vec<Complex<f32>> = Fi.iter().map(|e| e.conj()).collect();
-
-            // compute the initial filter
-            let top = self.target.iter().zip(Fi_star.iter()).map(|(g, f)| g * f);
-            let bottom = Fi.iter().zip(Fi_star.iter()).map(|(f, f_star)| f * f_star);
-
-            // // add the values to the running sum
-            self.last_top
-                .iter_mut()
-                .zip(top)
-                .for_each(|(running, new)| *running += new);
-
-            self.last_bottom
-                .iter_mut()
-                .zip(bottom)
-                .for_each(|(running, new)| *running += new);
-
-            training_frame_count += 1
+            Some(kalman_filter) => {
+                kalman_filter.correct((new_x, new_y));
+                self.current_target_center = (new_x, new_y);
+            }
+            None if !confident && self.redetection.is_some() => {}
+            None => self.current_target_center = (new_x, new_y),
         }
 
-        // divide the values of the top and bottom filters by the number of training perturbations used
-        self.last_top
-            .iter_mut()
-            .for_each(|e| *e /= training_frame_count as f32);
-
-        self.last_bottom
-            .iter_mut()
-            .for_each(|e| *e /= training_frame_count as f32);
+        // estimate the scale of the target at its new location, unless it is being searched for
+        let searching = !confident && self.redetection.is_some();
+        if let Some(scale_filter) = self.scale_filter.as_ref().filter(|_| !searching) {
+            self.current_scale = scale_filter.estimate(
+                frame,
+                self.current_target_center,
+                self.window_width,
+                self.window_height,
+                self.current_scale,
+            );
+        }
 
-        // compute the filter by dividing Ai and Bi elementwise
-        // note that we add a small quantity to avoid dividing by zero, which would yield NaN's.
-        self.filter = self
-            .last_top
-            .iter()
-            .zip(&self.last_bottom)
-            .map(|(a, b)| a / b + self.regularization)
-            .collect();
+        Ok(self.prediction())
+    }
 
-        #[cfg(debug_assertions)]
-        {
-            println!(
-                "current center of target in frame: x={}, y={}",
-                self.current_target_center.0, self.current_target_center.1
-            );
+    // the prediction for the current state of the tracker
+    fn prediction(&self) -> Prediction {
+        Prediction {
+            location: self.current_target_center,
+            psr: self.last_psr,
+            scale: self.current_scale,
+            left_frame: self.has_left_frame(),
+            motion: self.kalman_filter.as_ref().map(|k| k.estimate()),
+            missed_frames: self.missed_frames,
         }
     }
 
-    pub fn track_new_frame(&mut self, frame: &GrayImage) -> Prediction {
-        // cut out the training template by cropping
-        let window = window_crop(
+    // Look for the target in a detection window of the given dimensions (in window pixels, before scaling) around
+    // the search center. Returns the location of the correlation peak in the frame, and its PSR.
+    // The state of the tracker is left untouched.
+    fn locate<F: Frame>(
+        &self,
+        frame: &F,
+        search_width: u32,
+        search_height: u32,
+        search_center: (f32, f32),
+    ) -> Result<((f32, f32), f32), MosseError> {
+        let cell_size = self.features.cell_size().max(1);
+        let search_feature_width = (search_width / cell_size).max(self.feature_width);
+        let search_feature_height = (search_height / cell_size).max(self.feature_height);
+
+        // cut out the detection window by cropping, taking the current scale of the target into account
+        let window = scaled_window_crop(
             frame,
-            self.window_size,
-            self.window_size,
-            self.current_target_center,
+            search_width,
+            search_height,
+            self.current_scale,
+            search_center,
+            self.border_mode,
         );
 
         // preprocess the image using preprocess()
-        let vectorized = preprocess(&window);
-
-        // calculate the 2D FFT of the preprocessed image: FFT(fi) = Fi
-        let Fi = self.compute_2dfft(vectorized);
+        let vectorized = preprocess(
+            &*self.features,
+            &window,
+            search_feature_width,
+            search_feature_height,
+        );
 
-        // elementwise multiplication of F with filter H gives Gi
-        let mut corr_map_gi: Vec<Complex<f32>> =
-            Fi.iter().zip(&self.filter).map(|(a, b)| a * b).collect();
+        let corr_map_gi = if search_feature_width == self.feature_width
+            && search_feature_height == self.feature_height
+        {
+            self.correlate(vectorized)
+        } else if search_feature_width == self.search_feature_width
+            && search_feature_height == self.search_feature_height
+        {
+            self.correlate_padded(vectorized, &self.search_fft)
+        } else {
+            // the region searched for a missed target changes size from frame to frame, so its plans are not cached
+            let search_fft = Fft2d::uncached(search_feature_width, search_feature_height);
+            self.correlate_padded(vectorized, &search_fft)
+        };
 
-        // NOTE: Gi is garbage after this call
-        self.inv_fft.process(&mut corr_map_gi);
-
-        // find the max value of the filtered image 'gi', along with the position of the maximum
-        let (maxind, max_complex) = corr_map_gi
+        // find the max value of the filtered image 'gi', along with the position of the maximum,
+        // and convert its array index to the coordinates in the feature grid of the detection window
+        let empty_window = MosseError::InvalidWindowSize {
+            width: search_feature_width,
+            height: search_feature_height,
+        };
+        let (maxind, max_value) = corr_map_gi
             .iter()
             .enumerate()
-            .max_by(|a, b| {
-                // filtered (gi) is still complex at this point, we only care about the real part
-                a.1.re.partial_cmp(&b.1.re).unwrap_or(Ordering::Equal)
-            })
-            .unwrap(); // we can unwrap the result of max_by(), as we are sure filtered.len() > 0
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(Ordering::Equal))
+            .ok_or(empty_window.clone())?;
+        let max_coord_in_window =
+            index_to_coords(search_feature_width, maxind as u32).ok_or(empty_window)?;
+
+        // refine the location of the max to sub-pixel accuracy
+        let peak_in_window = subpixel_peak(
+            &corr_map_gi,
+            search_feature_width,
+            search_feature_height,
+            max_coord_in_window,
+        );
+
+        // the shift is measured in cells of the feature grid, so we scale it back to frame pixels
+        let cell_scale = cell_size as f32 * self.current_scale;
+        let x_delta = (peak_in_window.0 - (search_feature_width / 2) as f32) * cell_scale;
+        let y_delta = (peak_in_window.1 - (search_feature_height / 2) as f32) * cell_scale;
+
+        // compute PSR
+        // Note that we re-use the computed max and its coordinate for downstream simplicity
+        let psr = compute_psr(
+            &corr_map_gi,
+            search_feature_width,
+            search_feature_height,
+            *max_value,
+            max_coord_in_window,
+            self.psr_exclusion_size,
+        );
+
+        // compute the max coord in the frame by looking at the shift of the window center.
+        // Note that the window was cropped around the nearest whole pixel to the search center.
+        // The target is free to move up to and past the edges of the frame.
+        let x = search_center.0.round() + x_delta;
+        let y = search_center.1.round() + y_delta;
+
+        Ok(((x, y), psr))
+    }
 
-        // convert the array index of the max to the coordinates in the window
-        let max_coord_in_window = index_to_coords(self.window_size, maxind as u32);
+    // The dimensions (in window pixels, before scaling) and the center of the region to search for the target.
+    // While the target is missed, the region grows around its last known position until it covers the whole frame.
+    fn search_region(&self) -> (u32, u32, (f32, f32)) {
+        let redetection = match self.redetection {
+            Some(redetection) if self.missed_frames > 0 => redetection,
+            _ => {
+                return (
+                    self.search_width,
+                    self.search_height,
+                    self.current_target_center,
+                )
+            }
+        };
 
-        let window_half = (self.window_size / 2) as i32;
-        let x_delta = max_coord_in_window.0 as i32 - window_half;
-        let y_delta = max_coord_in_window.1 as i32 - window_half;
-        let x_max = self.frame_width as i32 - window_half;
-        let y_max = self.frame_height as i32 - window_half;
+        // the dimensions of the whole frame, measured in window pixels
+        let frame_width = ((self.frame_width as f32 / self.current_scale).round() as u32).max(1);
+        let frame_height = ((self.frame_height as f32 / self.current_scale).round() as u32).max(1);
 
-        #[cfg(debug_assertions)]
+        if redetection
+            .full_frame_after
+            .is_some_and(|frames| self.missed_frames >= frames)
         {
-            println!(
-                "distance of new in-window max from window center: x = {}, y = {}",
-                x_delta, y_delta,
+            let frame_center = (
+                self.frame_width as f32 / 2.0,
+                self.frame_height as f32 / 2.0,
+            );
+            return (
+                frame_width.max(self.window_width),
+                frame_height.max(self.window_height),
+                frame_center,
             );
         }
 
-        // compute the max coord in the frame by looking at the shift of the window center
-        let new_x = (self.current_target_center.0 as i32 + x_delta)
-            .min(x_max)
-            .max(window_half);
+        let growth = redetection.growth_factor.powi(self.missed_frames as i32);
+        let width = ((self.search_width as f32 * growth).round() as u32)
+            .min(frame_width)
+            .max(self.search_width);
+        let height = ((self.search_height as f32 * growth).round() as u32)
+            .min(frame_height)
+            .max(self.search_height);
+        (width, height, self.current_target_center)
+    }
 
-        let new_y = (self.current_target_center.1 as i32 + y_delta)
-            .min(y_max)
-            .max(window_half);
+    // frames must have the dimensions the tracker was configured with
+    fn check_frame<F: Frame>(&self, frame: &F) -> Result<(), MosseError> {
+        check_frame_size(frame, self.frame_width, self.frame_height)
+    }
 
-        self.current_target_center = (new_x as u32, new_y as u32);
+    // whether the center of the target lies outside of the frame
+    pub fn has_left_frame(&self) -> bool {
+        let (x, y) = self.current_target_center;
+        x < 0.0 || y < 0.0 || x >= self.frame_width as f32 || y >= self.frame_height as f32
+    }
 
-        // compute PSR
-        // Note that we re-use the computed max and its coordinate for downstream simplicity
-        self.last_psr = compute_psr(
-            &corr_map_gi,
-            self.window_size,
-            self.window_size,
-            max_complex.re,
-            max_coord_in_window,
-        );
+    // Look for a target that was lost, in the region the tracker would search on the next frame.
+    // On a confident detection inside the frame, the tracker resumes tracking the target there and its prediction
+    // is returned. Otherwise, the tracker keeps waiting at the last known position of the target.
+    fn reacquire<F: Frame>(&mut self, frame: &F) -> Result<Option<Prediction>, MosseError> {
+        self.missed_frames += 1;
+        let (search_width, search_height, search_center) = self.search_region();
+        let (location, psr) = self.locate(frame, search_width, search_height, search_center)?;
+
+        let (x, y) = location;
+        let inside =
+            x >= 0.0 && y >= 0.0 && x < self.frame_width as f32 && y < self.frame_height as f32;
+        if psr > self.psr_threshold && inside {
+            self.last_psr = psr;
+            self.resume(location);
+            Ok(Some(self.prediction()))
+        } else {
+            Ok(None)
+        }
+    }
 
-        Prediction {
-            location: self.current_target_center,
-            psr: self.last_psr,
+    // Whether this tracker confidently detects its target at the given location in a frame:
+    // the correlation peak has to lie within a quarter of the tracking window of the location.
+    fn recognizes<F: Frame>(&self, frame: &F, location: (f32, f32)) -> Result<bool, MosseError> {
+        let ((x, y), psr) = self.locate(frame, self.search_width, self.search_height, location)?;
+        let max_x_distance = self.window_width as f32 * self.current_scale / 4.0;
+        let max_y_distance = self.window_height as f32 * self.current_scale / 4.0;
+        Ok(psr > self.psr_threshold
+            && (x - location.0).abs() <= max_x_distance
+            && (y - location.1).abs() <= max_y_distance)
+    }
+
+    // Move the target to a location that was found by other means, like a detector, on the given frame of a
+    // multi-tracker, and learn its appearance there. If the tracker already measured and learned the target on that
+    // frame, where it tracked it, the location replaces that measurement and this update replaces that one, so the
+    // motion model is corrected once and the learning rate stays the same on frames with detections.
+    fn correct_location<F: Frame>(&mut self, frame: &F, frame_number: u64, location: (f32, f32)) {
+        match self.replaceable_frame.take() {
+            Some(replaceable) if replaceable.frame_number == frame_number => {
+                if let Some(model) = replaceable.model {
+                    self.filter = model.filter;
+                    self.last_top = model.last_top;
+                    self.last_bottom = model.last_bottom;
+                    self.kernel_filter = model.kernel_filter;
+                    self.scale_filter = model.scale_filter;
+                }
+                self.kalman_filter = match replaceable.kalman_filter {
+                    Some(mut kalman_filter) => {
+                        kalman_filter.predict();
+                        kalman_filter.correct(location);
+                        Some(kalman_filter)
+                    }
+                    None => self
+                        .motion_model
+                        .map(|model| KalmanFilter::new(model, location)),
+                };
+            }
+            _ => {
+                if let Some(kalman_filter) = &mut self.kalman_filter {
+                    kalman_filter.correct(location);
+                }
+            }
         }
+        self.current_target_center = location;
+        self.missed_frames = 0;
+        self.update(frame);
+    }
+
+    // track the target on the given frame of a multi-tracker, keeping the motion model for correct_location()
+    fn track_replaceably<F: Frame>(
+        &mut self,
+        frame: &F,
+        frame_number: u64,
+    ) -> Result<Prediction, MosseError> {
+        let kalman_filter = self.kalman_filter.clone();
+        let prediction = self.track_new_frame(frame)?;
+        self.replaceable_frame = Some(ReplaceableFrame {
+            frame_number,
+            kalman_filter,
+            model: None,
+        });
+        Ok(prediction)
+    }
+
+    // update the filter on the given frame of a multi-tracker, keeping what it learned before for correct_location()
+    fn update_replaceably<F: Frame>(&mut self, frame: &F, frame_number: u64) {
+        let model = LearnedModel {
+            filter: self.filter.clone(),
+            last_top: self.last_top.clone(),
+            last_bottom: self.last_bottom.clone(),
+            kernel_filter: self.kernel_filter.clone(),
+            scale_filter: self.scale_filter.clone(),
+        };
+        match &mut self.replaceable_frame {
+            Some(replaceable) if replaceable.frame_number == frame_number => {
+                replaceable.model = Some(model)
+            }
+            // a dormant tracker that found its target, and started its motion model over
+            _ => {
+                self.replaceable_frame = Some(ReplaceableFrame {
+                    frame_number,
+                    kalman_filter: None,
+                    model: Some(model),
+                })
+            }
+        }
+        self.update(frame);
+    }
+
+    // continue tracking a target that was lost from the given location, keeping the learned filter
+    fn resume(&mut self, location: (f32, f32)) {
+        self.current_target_center = location;
+        self.missed_frames = 0;
+        self.kalman_filter = self
+            .motion_model
+            .map(|model| KalmanFilter::new(model, location));
+    }
+
+    // Correlate the filter with a preprocessed window of the size of the tracking window.
+    // Returns the (real) correlation map.
+    fn correlate(&self, vectorized: Vec<Vec<f32>>) -> Vec<f32> {
+        if let Some(kernel_filter) = &self.kernel_filter {
+            return kernel_filter.correlate(&self.fft, vectorized);
+        }
+
+        // elementwise multiplication of each channel F with its filter H, summed over all channels, gives Gi
+        let mut Gi = vec![Complex::zero(); self.fft.spectrum_len()];
+        for (channel, filter) in vectorized.into_iter().zip(&self.filter) {
+            // calculate the 2D FFT of the preprocessed channel: FFT(fi) = Fi
+            let Fi = self.compute_2dfft(channel);
+            Gi.iter_mut()
+                .zip(Fi.iter().zip(filter))
+                .for_each(|(g, (a, b))| *g += a * b);
+        }
+
+        // transform Gi back to the spatial domain, which gives the (real) correlation map gi
+        // NOTE: Gi is garbage after this call
+        self.fft.inverse(&mut Gi)
+    }
+
+    // Correlate the filter with a preprocessed detection window that is larger than the tracking window,
+    // using the FFT plans for the feature grid of the detection window.
+    // The filter is zero-padded to the size of the detection window, so it slides over all of it.
+    // Returns the (real) correlation map over the detection window.
+    fn correlate_padded(&self, mut vectorized: Vec<Vec<f32>>, search_fft: &Fft2d) -> Vec<f32> {
+        // The features are normalized over the whole detection window. Scale them up, so every part of the window
+        // the size of the tracking window has roughly the same norm as the windows the filter was trained on.
+        let norm_factor = (search_fft.len() as f32 / self.fft.len() as f32).sqrt();
+        vectorized
+            .iter_mut()
+            .flatten()
+            .for_each(|v| *v *= norm_factor);
+
+        if let Some(kernel_filter) = &self.kernel_filter {
+            return kernel_filter.correlate_padded(&self.fft, search_fft, vectorized);
+        }
+
+        let mut Gi = vec![Complex::zero(); search_fft.spectrum_len()];
+        for (mut channel, filter) in vectorized.into_iter().zip(&self.filter) {
+            // the spatial filter is centered on its origin, so it wraps around the edges of the detection window
+            // NOTE: the input of the inverse transform is garbage after the call, so we clone the filter first.
+            let h: Vec<f32> = self
+                .fft
+                .inverse(&mut filter.clone())
+                .into_iter()
+                .map(|v| v / self.fft.len() as f32)
+                .collect();
+            let H = search_fft.forward(&mut search_fft.pad_circular(&h, &self.fft));
+
+            let Fi = search_fft.forward(&mut channel);
+            Gi.iter_mut()
+                .zip(Fi.iter().zip(&H))
+                .for_each(|(g, (a, b))| *g += a * b);
+        }
+
+        // NOTE: Gi is garbage after this call
+        search_fft.inverse(&mut Gi)
     }
 
     // update the filter
-    fn update(&mut self, frame: &GrayImage) {
-        // cut out the training template by cropping
-        let window = window_crop(
+    fn update<F: Frame>(&mut self, frame: &F) {
+        // cut out the training template by cropping, taking the current scale of the target into account
+        let window = scaled_window_crop(
             frame,
-            self.window_size,
-            self.window_size,
+            self.window_width,
+            self.window_height,
+            self.current_scale,
             self.current_target_center,
+            self.border_mode,
         );
 
         // preprocess the image using preprocess()
-        let vectorized = preprocess(&window);
+        let vectorized = preprocess(
+            &*self.features,
+            &window,
+            self.feature_width,
+            self.feature_height,
+        );
+
+        match &mut self.kernel_filter {
+            Some(kernel_filter) => kernel_filter.update(&self.fft, vectorized, &self.target),
+            None => self.update_linear_filter(vectorized),
+        }
+
+        // update the scale filter
+        if let Some(scale_filter) = &mut self.scale_filter {
+            scale_filter.update(
+                frame,
+                self.current_target_center,
+                self.window_width,
+                self.window_height,
+                self.current_scale,
+            );
+        }
+    }
+
+    // Train the linear (MOSSE) filter on the window and a set of slightly perturbed copies of it
+    fn train_linear_filter(&mut self, window: &RgbImage) {
+        // build an iterator that produces training frames that have been slightly rotated according to a theta value.
+        let rotated_frames = self.augmentation.rotations.iter().map(|rad| {
+            // Rotate an image clockwise about its center by theta radians.
+            rotate_about_center(window, *rad, Interpolation::Nearest, Rgb([0, 0, 0]))
+        });
+
+        // build an iterator that produces training frames that have been slightly scaled to various degrees ('zoomed')
+        let scaled_frames = self.augmentation.scales.iter().map(|scalefactor| {
+            let scale = Projection::scale(*scalefactor, *scalefactor);
+
+            warp(window, &scale, Interpolation::Nearest, Rgb([0, 0, 0]))
+        });
+
+        // Chain these iterators together.
+        // Note that we add the initial, unperturbed training frame as first in line.
+        let training_frames = std::iter::once(window)
+            .cloned()
+            .chain(rotated_frames)
+            .chain(scaled_frames);
+        // TODO: scaling is not ready yet
+        // .chain(scaled_frames);
+
+        let mut training_frame_count = 0;
+        for training_frame in training_frames {
+            // preprocess the training frame using preprocess()
+            let vectorized = preprocess(
+                &*self.features,
+                &training_frame,
+                self.feature_width,
+                self.feature_height,
+            );
+
+            for (mut channel, running_top) in vectorized.into_iter().zip(self.last_top.iter_mut()) {
+                // calculate the 2D FFT of the preprocessed channel: FFT(fi) = Fi
+                let Fi = self.fft.forward(&mut channel);
+
+                //  compute the complex conjugate of Fi, Fi*.
+                let Fi_star: Vec<Complex<f32>> = Fi.iter().map(|e| e.conj()).collect();
 
-        // calculate the 2D FFT of the preprocessed image: FFT(fi) = Fi
-        let new_Fi = self.compute_2dfft(vectorized);
+                // compute the initial filter. The bottom is shared by all channels.
+                let top = self.target.iter().zip(Fi_star.iter()).map(|(g, f)| g * f);
+                let bottom = Fi.iter().zip(Fi_star.iter()).map(|(f, f_star)| f * f_star);
 
-        //// Update the filter using the prediction
-        //  compute the complex conjugate of Fi, Fi*.
-        let Fi_star: Vec<Complex<f32>> = new_Fi.iter().map(|e| e.conj()).collect();
+                // // add the values to the running sum
+                running_top
+                    .iter_mut()
+                    .zip(top)
+                    .for_each(|(running, new)| *running += new);
 
+                self.last_bottom
+                    .iter_mut()
+                    .zip(bottom)
+                    .for_each(|(running, new)| *running += new);
+            }
+
+            training_frame_count += 1
+        }
+
+        // divide the values of the top and bottom filters by the number of training perturbations used
+        self.last_top
+            .iter_mut()
+            .flatten()
+            .for_each(|e| *e /= training_frame_count as f32);
+
+        self.last_bottom
+            .iter_mut()
+            .for_each(|e| *e /= training_frame_count as f32);
+
+        self.solve_linear_filter();
+    }
+
+    // Update the linear (MOSSE) filter with a preprocessed window, using the running average of the paper
+    fn update_linear_filter(&mut self, vectorized: Vec<Vec<f32>>) {
         // compute Ai (top) and Bi (bottom) using F*, G, and the learning rate (see paper)
         let one_minus_eta = 1.0 - self.eta;
 
-        // update the 'top' of the filter update equation
-        self.last_top = self
-            .target
-            .iter()
-            .zip(&Fi_star)
-            .zip(&self.last_top)
-            .map(|((g, f), prev)| self.eta * (g * f) + (one_minus_eta * prev))
-            .collect();
+        // the 'bottom' of the filter update equation is summed over all channels
+        let mut new_bottom = vec![Complex::zero(); self.fft.spectrum_len()];
+
+        for (mut channel, prev_top) in vectorized.into_iter().zip(self.last_top.iter_mut()) {
+            // calculate the 2D FFT of the preprocessed channel: FFT(fi) = Fi
+            let new_Fi = self.fft.forward(&mut channel);
+
+            //// Update the filter using the prediction
+            //  compute the complex conjugate of Fi, Fi*.
+            let Fi_star: Vec<Complex<f32>> = new_Fi.iter().map(|e| e.conj()).collect();
+
+            // update the 'top' of the filter update equation
+            prev_top
+                .iter_mut()
+                .zip(self.target.iter().zip(&Fi_star))
+                .for_each(|(prev, (g, f))| *prev = self.eta * (g * f) + (one_minus_eta * *prev));
+
+            new_bottom
+                .iter_mut()
+                .zip(new_Fi.iter().zip(&Fi_star))
+                .for_each(|(b, (f, f_star))| *b += f * f_star);
+        }
 
         // update the 'bottom' of the filter update equation
-        self.last_bottom = new_Fi
+        self.last_bottom = new_bottom
             .iter()
-            .zip(&Fi_star)
             .zip(&self.last_bottom)
-            .map(|((f, f_star), prev)| self.eta * (f * f_star) + (one_minus_eta * prev))
+            .map(|(new, prev)| self.eta * new + (one_minus_eta * prev))
             .collect();
 
-        // compute the new filter H* by dividing Ai and Bi elementwise
+        self.solve_linear_filter();
+    }
+
+    // compute the filter H* of each channel by dividing Ai and Bi elementwise
+    // note that we add a small quantity to the divisor to avoid dividing by zero, which would yield NaN's.
+    fn solve_linear_filter(&mut self) {
         self.filter = self
             .last_top
             .iter()
-            .zip(&self.last_bottom)
-            .map(|(a, b)| a / b)
+            .map(|top| {
+                top.iter()
+                    .zip(&self.last_bottom)
+                    .map(|(a, b)| a / (b + self.regularization))
+                    .collect()
+            })
             .collect();
     }
 
     // debug method to dump the latest filter to an inspectable image
-    pub fn dump_filter(
-        &self,
-    ) -> (
-        ImageBuffer<Luma<u8>, Vec<u8>>,
-        ImageBuffer<Luma<u8>, Vec<u8>>,
-    ) {
-        // get the filter out of fourier space
-        // NOTE: input is garbage after this call to inv_fft.process(), so we clone the filter first.
-        let mut h = self.filter.clone();
-        self.inv_fft.process(&mut h);
+    // note that the filter is real in the spatial domain, as it is trained on real input.
+    // The filters of multiple feature channels are summed, so the image covers the feature grid.
+    // For a kernelized filter, this dumps its dual coefficients (alpha) instead.
+    pub fn dump_filter(&self) -> Result<GrayImage, MosseError> {
+        let mut filter: Vec<Complex<f32>> = match &self.kernel_filter {
+            Some(kernel_filter) => kernel_filter.dual_coefficients().clone(),
+            None => {
+                let mut sum = vec![Complex::zero(); self.fft.spectrum_len()];
+                for channel in &self.filter {
+                    sum.iter_mut().zip(channel).for_each(|(s, h)| *s += h);
+                }
+                sum
+            }
+        };
 
-        // turn the real and imaginary values of the filter into separate grayscale images
-        let realfilter = h.iter().map(|c| c.re).collect();
-        let imfilter = h.iter().map(|c| c.im).collect();
+        // get the filter out of fourier space
+        // NOTE: the input of the inverse transform is garbage after the call
+        let h = self.fft.inverse(&mut filter);
 
-        (
-            to_imgbuf(&realfilter, self.window_size, self.window_size),
-            to_imgbuf(&imfilter, self.window_size, self.window_size),
-        )
+        to_imgbuf(&h, self.feature_width, self.feature_height)
     }
 }
 
-fn window_crop(
-    input_frame: &GrayImage,
+// crop a window around the center. Parts of the window outside of the frame are filled in according to the border mode.
+fn window_crop<F: Frame>(
+    input_frame: &F,
     window_width: u32,
     window_height: u32,
-    center: (u32, u32),
-) -> GrayImage {
-    let window = imageops::crop(
-        &mut input_frame.clone(),
-        center
-            .0
-            .saturating_sub(window_width / 2)
-            .min(input_frame.width() - window_width),
-        center
-            .1
-            .saturating_sub(window_height / 2)
-            .min(input_frame.height() - window_height),
+    center: (f32, f32),
+    border_mode: BorderMode,
+) -> RgbImage {
+    // the window is centered on the nearest whole pixel
+    let center = (center.0.round() as i64, center.1.round() as i64);
+    input_frame.crop_rgb(
+        center.0 - (window_width / 2) as i64,
+        center.1 - (window_height / 2) as i64,
         window_width,
         window_height,
+        border_mode,
     )
-    .to_image();
+}
+
+// crop a window of (window_width * scale) by (window_height * scale) pixels around the center,
+// and resample it to window_width by window_height pixels.
+fn scaled_window_crop<F: Frame>(
+    input_frame: &F,
+    window_width: u32,
+    window_height: u32,
+    scale: f32,
+    center: (f32, f32),
+    border_mode: BorderMode,
+) -> RgbImage {
+    let patch = window_crop(
+        input_frame,
+        ((window_width as f32 * scale).round() as u32).max(1),
+        ((window_height as f32 * scale).round() as u32).max(1),
+        center,
+        border_mode,
+    );
+
+    if patch.dimensions() == (window_width, window_height) {
+        return patch;
+    }
 
-    window
+    imageops::resize(&patch, window_width, window_height, FilterType::Triangle)
 }
 
-fn build_target(window_width: u32, window_height: u32) -> Vec<f32> {
+fn build_target(window_width: u32, window_height: u32, sigma: f32) -> Vec<f32> {
     let mut target_gi = vec![0f32; (window_width * window_height) as usize];
 
     // Optional: let the sigma depend on the window size (Galoogahi et al. (2015). Correlation Filters with Limited Boundaries)
     // let sigma = ((window_width * window_height) as f32).sqrt() / 16.0;
-    // let variance = sigma * sigma;
-    let variance = 2.0;
+    let variance = sigma * sigma;
 
     // create gaussian peak at the center coordinates
     let center_x = window_width / 2;
@@ -579,7 +1857,7 @@ fn build_target(window_width: u32, window_height: u32) -> Vec<f32> {
 
             // apply a crude univariate Gaussian density function
             target_gi[((y * window_width) + x) as usize] =
-                (-((distx * distx) + (disty * disty) / variance)).exp()
+                (-((distx * distx) + (disty * disty)) / (2.0 * variance)).exp()
         }
     }
 
@@ -588,67 +1866,132 @@ fn build_target(window_width: u32, window_height: u32) -> Vec<f32> {
 
 // function for debugging the shape of the target
 // output only depends on the provided target_coords
-pub fn dump_target(window_width: u32, window_height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    let trgt = build_target(window_width, window_height);
+pub fn dump_target(
+    window_width: u32,
+    window_height: u32,
+    sigma: f32,
+) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, MosseError> {
+    let trgt = build_target(window_width, window_height, sigma);
 
-    let normalized = trgt.iter().map(|a| a * 255.0).collect();
+    let normalized: Vec<f32> = trgt.iter().map(|a| a * 255.0).collect();
 
     to_imgbuf(&normalized, window_width, window_height)
 }
 
+// Fit a parabola through the max of the correlation map and its direct neighbours, separately along each axis.
+// The vertex of the parabola is the sub-pixel location of the peak.
+fn subpixel_peak(predicted: &[f32], width: u32, height: u32, maxpos: (u32, u32)) -> (f32, f32) {
+    let value = |x: u32, y: u32| predicted[(y * width + x) as usize];
+
+    // the correlation is circular, so the neighbours of a max on the border wrap around
+    let vertex_offset = |before: f32, max: f32, after: f32| {
+        let curvature = before - 2.0 * max + after;
+        if curvature < 0.0 {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        }
+    };
+
+    let (x, y) = maxpos;
+    let max = value(x, y);
+    let x_offset = vertex_offset(
+        value((x + width - 1) % width, y),
+        max,
+        value((x + 1) % width, y),
+    );
+    let y_offset = vertex_offset(
+        value(x, (y + height - 1) % height),
+        max,
+        value(x, (y + 1) % height),
+    );
+
+    (x as f32 + x_offset, y as f32 + y_offset)
+}
+
+// The peak-to-sidelobe ratio of the correlation map: the height of the peak in standard deviations above the mean of
+// the sidelobe, which is everything outside a square of exclusion_size cells around the peak.
 fn compute_psr(
-    predicted: &Vec<Complex<f32>>,
+    predicted: &[f32],
     width: u32,
     height: u32,
     max: f32,
     maxpos: (u32, u32),
+    exclusion_size: u32,
 ) -> f32 {
     // uses running updates of standard deviation and mean
     let mut running_sum = 0.0;
     let mut running_sd = 0.0;
     for e in predicted {
-        running_sum += e.re;
-        running_sd += e.re * e.re;
-    }
-
-    // subtract the values of a 11*11 window around the max from the running sd and sum
-    // TODO: look up: why 11*11, and not something simpler like 12*12?
-    let max_x = maxpos.0 as i32;
-    let max_y = maxpos.1 as i32;
-    let window_left = (max_x - 5).max(0);
-    let window_right = (max_x + 6).min(width as i32);
-    let window_top = (max_y - 5).min(0); // note: named according to CG conventions
-    let window_bottom = (max_y + 6).min(height as i32);
+        running_sum += e;
+        running_sd += e * e;
+    }
+
+    // subtract the values of the window around the max from the running sd and sum
+    let max_x = maxpos.0 as i64;
+    let max_y = maxpos.1 as i64;
+    let half = (exclusion_size / 2) as i64;
+    let window_left = (max_x - half).max(0);
+    let window_right = (max_x - half + exclusion_size as i64).min(width as i64);
+    let window_top = (max_y - half).max(0); // note: named according to CG conventions
+    let window_bottom = (max_y - half + exclusion_size as i64).min(height as i64);
+    let mut excluded = 0;
     for x in window_left..window_right {
-        for y in window_bottom..window_top {
-            let ind = (y * width as i32 + x) as usize;
-            let val = predicted[ind].re;
+        for y in window_top..window_bottom {
+            let ind = (y * width as i64 + x) as usize;
+            let val = predicted[ind];
             running_sd -= val * val;
             running_sum -= val;
+            excluded += 1;
         }
     }
 
-    // we need to subtract 11*11 window from predicted.len() to get the sidelobe_size
-    let sidelobe_size = (predicted.len() - (11 * 11)) as f32;
+    // Note that the correlation map may not be larger than the excluded window.
+    let sidelobe_size = predicted.len().saturating_sub(excluded).max(1) as f32;
     let mean_sl = running_sum / sidelobe_size;
     let sd_sl = ((running_sd / sidelobe_size) - (mean_sl * mean_sl)).sqrt();
 
-    (max - mean_sl) / sd_sl
+    // a flat correlation map, like that of a frame without any contrast, has no peak to speak of
+    if sd_sl > 0.0 {
+        (max - mean_sl) / sd_sl
+    } else {
+        0.0
+    }
 }
 
-fn index_to_coords(width: u32, index: u32) -> (u32, u32) {
+// returns None if the width is zero, which would indicate an upstream error
+fn index_to_coords(width: u32, index: u32) -> Option<(u32, u32)> {
     // modulo/remainder ops are theoretically O(1)
-    // checked_rem returns None if rhs == 0, which would indicate an upstream error (width == 0).
-    let x = index.checked_rem(width).unwrap();
+    let x = index.checked_rem(width)?;
+    let y = index.checked_div(width)?;
+    Some((x, y))
+}
 
-    // checked sub returns None if overflow occurred, which is also a panicable offense.
-    // checked_div returns None if rhs == 0, which would indicate an upstream error (width == 0).
-    let y = (index.checked_sub(x).unwrap()).checked_div(width).unwrap();
-    (x, y)
+pub fn to_imgbuf(
+    buf: &[f32],
+    width: u32,
+    height: u32,
+) -> Result<ImageBuffer<Luma<u8>, Vec<u8>>, MosseError> {
+    let expected = width as usize * height as usize;
+    ImageBuffer::from_vec(width, height, buf.iter().map(|c| *c as u8).collect()).ok_or(
+        MosseError::BufferSizeMismatch {
+            expected,
+            actual: buf.len(),
+        },
+    )
 }
 
-pub fn to_imgbuf(buf: &Vec<f32>, width: u32, height: u32) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    ImageBuffer::from_vec(width, height, buf.iter().map(|c| *c as u8).collect()).unwrap()
+// frames must have the given dimensions
+fn check_frame_size<F: Frame>(frame: &F, width: u32, height: u32) -> Result<(), MosseError> {
+    let actual = frame.dimensions();
+    if actual == (width, height) {
+        Ok(())
+    } else {
+        Err(MosseError::FrameSizeMismatch {
+            expected: (width, height),
+            actual,
+        })
+    }
 }
 
 // TODO: below tests are used as a scratch pad and for syntax experiments, not serious unit testing.
@@ -684,46 +2027,1736 @@ mod tests {
         let width = 64;
         let height = 64;
         let frame = GrayImage::new(width, height);
-        let settings = MosseTrackerSettings {
-            window_size: 16,
-            width,
-            height,
-            regularization: 0.001,
-            learning_rate: 0.05,
-            psr_threshold: 7.0,
-        };
-        let mut multi_tracker = MultiMosseTracker::new(settings, 3);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(16, 16)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
         assert_eq!(multi_tracker.size(), 0);
-        multi_tracker.add_or_replace_target(0, (0, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(0, (0.0, 0.0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 1);
         assert_eq!(
             multi_tracker
                 .trackers
                 .iter()
-                .find(|t| t.0 == 0)
+                .find(|t| t.id == 0)
                 .unwrap()
-                .2
+                .tracker
                 .current_target_center,
-            (0, 0)
+            (0.0, 0.0)
         );
 
-        multi_tracker.add_or_replace_target(1, (10, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(1, (10.0, 0.0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 2);
 
-        multi_tracker.add_or_replace_target(0, (10, 0), &frame);
+        multi_tracker
+            .add_or_replace_target(0, (10.0, 0.0), &frame)
+            .unwrap();
 
         assert_eq!(multi_tracker.size(), 2);
         assert_eq!(
             multi_tracker
                 .trackers
                 .iter()
-                .find(|t| t.0 == 0)
+                .find(|t| t.id == 0)
                 .unwrap()
-                .2
+                .tracker
                 .current_target_center,
-            (10, 0)
+            (10.0, 0.0)
+        );
+    }
+
+    // draws a bright, textured rectangle centered on the given coordinates onto a dark frame
+    fn frame_with_blob(
+        width: u32,
+        height: u32,
+        center: (u32, u32),
+        blob_width: u32,
+        blob_height: u32,
+    ) -> GrayImage {
+        let mut frame = GrayImage::new(width, height);
+        let left = center.0 - blob_width / 2;
+        let top = center.1 - blob_height / 2;
+        for x in left..left + blob_width {
+            for y in top..top + blob_height {
+                let texture = ((x - left) * 7 + (y - top) * 13) % 64;
+                frame.put_pixel(x, y, Luma([160 + texture as u8]));
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn rectangular_window() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(24, 48)
+            .build()
+            .unwrap();
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker
+            .train(
+                &frame_with_blob(width, height, (64, 64), 10, 30),
+                (64.0, 64.0),
+            )
+            .unwrap();
+
+        let real = tracker.dump_filter().unwrap();
+        assert_eq!(real.dimensions(), (24, 48));
+
+        let pred = tracker
+            .track_new_frame(&frame_with_blob(width, height, (67, 62), 10, 30))
+            .unwrap();
+        assert!((pred.location.0 - 67.0).abs() < 0.5, "{:?}", pred.location);
+        assert!((pred.location.1 - 62.0).abs() < 0.5, "{:?}", pred.location);
+    }
+
+    // draws a disc of concentric rings centered on the given coordinates onto a dark frame
+    fn frame_with_disc(width: u32, height: u32, center: (f32, f32), radius: f32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| {
+            let dx = x as f32 - center.0;
+            let dy = y as f32 - center.1;
+            let dist = (dx * dx + dy * dy).sqrt() / radius;
+            let rings = (dist * 3.0 * f32::consts::PI).cos();
+            Luma([if dist < 1.0 {
+                (127.0 + 127.0 * rings) as u8
+            } else {
+                0
+            }])
+        })
+    }
+
+    #[test]
+    fn scale_estimation() {
+        let (width, height) = (160, 160);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .scale_estimation(ScaleEstimationSettings::default())
+            .build()
+            .unwrap();
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker
+            .train(
+                &frame_with_disc(width, height, (80.0, 80.0), 14.0),
+                (80.0, 80.0),
+            )
+            .unwrap();
+
+        let pred = tracker
+            .track_new_frame(&frame_with_disc(width, height, (80.0, 80.0), 14.0))
+            .unwrap();
+        assert_eq!(pred.scale, 1.0);
+
+        // the scale estimate converges on the new size of the target over a couple of frames
+        let grown = frame_with_disc(width, height, (80.0, 80.0), 16.0);
+        for _ in 0..10 {
+            tracker.track_new_frame(&grown).unwrap();
+        }
+        let pred = tracker.track_new_frame(&grown).unwrap();
+        assert!(pred.scale > 1.05, "scale: {}", pred.scale);
+    }
+
+    #[test]
+    fn subpixel_location() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .build()
+            .unwrap();
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker
+            .train(
+                &frame_with_disc(width, height, (64.0, 64.0), 12.0),
+                (64.0, 64.0),
+            )
+            .unwrap();
+
+        let pred = tracker
+            .track_new_frame(&frame_with_disc(width, height, (65.4, 62.7), 12.0))
+            .unwrap();
+        assert!((pred.location.0 - 65.4).abs() < 0.3, "{:?}", pred.location);
+        assert!((pred.location.1 - 62.7).abs() < 0.3, "{:?}", pred.location);
+    }
+
+    #[test]
+    fn kernelized_filter() {
+        let (width, height) = (128, 128);
+        for kernel in [
+            Kernel::Gaussian { sigma: 0.5 },
+            Kernel::Polynomial {
+                additive: 1.0,
+                exponent: 7,
+            },
+        ] {
+            let settings = MosseTrackerSettings::builder(width, height)
+                .window_size(48, 48)
+                .regularization(0.0001)
+                .learning_rate(0.075)
+                .kernel(kernel)
+                .build()
+                .unwrap();
+            let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+            multi_tracker
+                .add_or_replace_target(
+                    0,
+                    (64.0, 64.0),
+                    &frame_with_disc(width, height, (64.0, 64.0), 12.0),
+                )
+                .unwrap();
+
+            let predictions = multi_tracker
+                .track(&frame_with_disc(width, height, (68.0, 61.0), 12.0))
+                .unwrap();
+            let (_, pred) = &predictions[0];
+            assert!((pred.location.0 - 68.0).abs() < 0.5, "{:?}", pred.location);
+            assert!((pred.location.1 - 61.0).abs() < 0.5, "{:?}", pred.location);
+        }
+    }
+
+    #[test]
+    fn hog_features() {
+        let (width, height) = (128, 128);
+        for kernel in [None, Some(Kernel::Gaussian { sigma: 0.5 })] {
+            let settings = MosseTrackerSettings {
+                kernel,
+                ..MosseTrackerSettings::builder(width, height)
+                    .window_size(48, 48)
+                    .regularization(0.0001)
+                    .learning_rate(0.075)
+                    .features(Arc::new(Hog::default()))
+                    .build()
+                    .unwrap()
+            };
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker
+                .train(
+                    &frame_with_disc(width, height, (64.0, 64.0), 12.0),
+                    (64.0, 64.0),
+                )
+                .unwrap();
+
+            // the filter covers the grid of 4x4 pixel cells
+            assert_eq!(tracker.dump_filter().unwrap().dimensions(), (12, 12));
+
+            let pred = tracker
+                .track_new_frame(&frame_with_disc(width, height, (68.0, 60.0), 12.0))
+                .unwrap();
+            assert!((pred.location.0 - 68.0).abs() < 1.5, "{:?}", pred.location);
+            assert!((pred.location.1 - 60.0).abs() < 1.5, "{:?}", pred.location);
+        }
+    }
+
+    // draws an orange square on a gray background of the same brightness, so it is only visible in colour
+    fn frame_with_colour_blob(width: u32, height: u32, center: (u32, u32), size: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            if x.abs_diff(center.0) <= size / 2 && y.abs_diff(center.1) <= size / 2 {
+                Rgb([255, 100, 32])
+            } else {
+                Rgb([128, 128, 128])
+            }
+        })
+    }
+
+    #[test]
+    fn colour_features() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .features(Arc::new(OpponentColor::default()))
+            .build()
+            .unwrap();
+        let frame = frame_with_colour_blob(width, height, (64, 64), 16);
+        assert!(imageops::grayscale(&frame)
+            .pixels()
+            .all(|p| p[0].abs_diff(128) <= 1));
+
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker.train(&frame, (64.0, 64.0)).unwrap();
+
+        let pred = tracker
+            .track_new_frame(&frame_with_colour_blob(width, height, (67, 61), 16))
+            .unwrap();
+        assert!((pred.location.0 - 67.0).abs() < 0.5, "{:?}", pred.location);
+        assert!((pred.location.1 - 61.0).abs() < 0.5, "{:?}", pred.location);
+    }
+
+    #[test]
+    fn search_area() {
+        let (width, height) = (200, 200);
+        for kernel in [None, Some(Kernel::Gaussian { sigma: 0.5 })] {
+            let settings = MosseTrackerSettings {
+                kernel,
+                ..MosseTrackerSettings::builder(width, height)
+                    .window_size(48, 48)
+                    .search_area_factor(2.5)
+                    .build()
+                    .unwrap()
+            };
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker
+                .train(
+                    &frame_with_disc(width, height, (100.0, 100.0), 12.0),
+                    (100.0, 100.0),
+                )
+                .unwrap();
+
+            // the target moves further than half the tracking window
+            let pred = tracker
+                .track_new_frame(&frame_with_disc(width, height, (130.0, 75.0), 12.0))
+                .unwrap();
+            assert!((pred.location.0 - 130.0).abs() < 0.5, "{:?}", pred.location);
+            assert!((pred.location.1 - 75.0).abs() < 0.5, "{:?}", pred.location);
+        }
+    }
+
+    #[test]
+    fn border_modes() {
+        // a frame of five pixels wide, and a window from three pixels left of it to three pixels right of it
+        let coordinates: Vec<Option<u32>> = (-3..8)
+            .map(|x| BorderMode::Mirror.source_coordinate(x, 5))
+            .collect();
+        let expected = [3, 2, 1, 0, 1, 2, 3, 4, 3, 2, 1].map(Some);
+        assert_eq!(coordinates, expected);
+
+        assert_eq!(BorderMode::Replicate.source_coordinate(-2, 5), Some(0));
+        assert_eq!(BorderMode::Replicate.source_coordinate(7, 5), Some(4));
+        assert_eq!(BorderMode::Zero.source_coordinate(-1, 5), None);
+        assert_eq!(BorderMode::Mean.source_coordinate(5, 5), None);
+
+        let frame = GrayImage::from_fn(4, 4, |x, _| Luma([if x < 2 { 10 } else { 30 }]));
+        let window = frame.crop_rgb(2, -2, 4, 4, BorderMode::Mean);
+        assert_eq!(window.get_pixel(0, 0), &Rgb([30, 30, 30]));
+        let window = frame.crop_rgb(-2, -2, 4, 4, BorderMode::Mean);
+        assert_eq!(window.get_pixel(3, 0), &Rgb([10, 10, 10]));
+    }
+
+    #[test]
+    fn leaving_the_frame() {
+        let (width, height) = (128, 128);
+        for border_mode in [
+            BorderMode::Zero,
+            BorderMode::Replicate,
+            BorderMode::Mirror,
+            BorderMode::Mean,
+        ] {
+            let settings = MosseTrackerSettings {
+                border_mode,
+                ..MosseTrackerSettings::builder(width, height)
+                    .window_size(48, 48)
+                    .build()
+                    .unwrap()
+            };
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker
+                .train(
+                    &frame_with_disc(width, height, (100.0, 64.0), 12.0),
+                    (100.0, 64.0),
+                )
+                .unwrap();
+
+            // the target is not pinned to the position where its window touches the edge of the frame,
+            // and it is reported once its center leaves the frame
+            for x in [104.0, 108.0, 112.0, 116.0, 120.0, 124.0, 128.0, 132.0] {
+                let frame = frame_with_disc(width, height, (x, 64.0), 12.0);
+                let pred = tracker.track_new_frame(&frame).unwrap();
+                tracker.update(&frame);
+                assert!(
+                    (pred.location.0 - x).abs() < 1.0,
+                    "{:?}: {:?}",
+                    border_mode,
+                    pred.location
+                );
+                assert_eq!(pred.left_frame, pred.location.0 >= width as f32);
+            }
+            assert!(tracker.has_left_frame());
+        }
+    }
+
+    #[test]
+    fn motion_model() {
+        let (width, height) = (400, 128);
+        for (motion_model, order) in [
+            (
+                MotionModel::ConstantVelocity {
+                    process_noise: 1.0,
+                    measurement_noise: 1.0,
+                },
+                2,
+            ),
+            (
+                MotionModel::ConstantAcceleration {
+                    process_noise: 1.0,
+                    measurement_noise: 1.0,
+                },
+                3,
+            ),
+        ] {
+            let settings = MosseTrackerSettings::builder(width, height)
+                .window_size(48, 48)
+                .motion_model(motion_model)
+                .build()
+                .unwrap();
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker
+                .train(
+                    &frame_with_disc(width, height, (40.0, 64.0), 12.0),
+                    (40.0, 64.0),
+                )
+                .unwrap();
+
+            // the target speeds up to a velocity at which it would leave the window between two frames
+            let mut x = 40.0;
+            for velocity in [4.0, 8.0, 12.0, 16.0, 20.0, 24.0, 28.0, 28.0, 28.0, 28.0] {
+                x += velocity;
+                let frame = frame_with_disc(width, height, (x, 64.0), 12.0);
+                let pred = tracker.track_new_frame(&frame).unwrap();
+                tracker.update(&frame);
+                assert!(
+                    (pred.location.0 - x).abs() < 1.0,
+                    "{} {:?}",
+                    x,
+                    pred.location
+                );
+            }
+
+            let pred = tracker
+                .track_new_frame(&frame_with_disc(width, height, (x + 28.0, 64.0), 12.0))
+                .unwrap();
+            let motion = pred.motion.unwrap();
+            assert!((motion.velocity.0 - 28.0).abs() < 2.0, "{:?}", motion);
+            assert!(motion.velocity.1.abs() < 1.0, "{:?}", motion);
+            assert_eq!(motion.covariance.0.len(), order);
+        }
+    }
+
+    #[test]
+    fn redetection() {
+        let (width, height) = (256, 256);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .redetection(RedetectionSettings {
+                growth_factor: 1.5,
+                full_frame_after: Some(3),
+            })
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 10);
+        multi_tracker
+            .add_or_replace_target(
+                0,
+                (64.0, 64.0),
+                &frame_with_disc(width, height, (64.0, 64.0), 12.0),
+            )
+            .unwrap();
+
+        // the target disappears for a couple of frames
+        let empty = GrayImage::new(width, height);
+        for missed in 1..=4 {
+            let (_, pred) = &multi_tracker.track(&empty).unwrap()[0];
+            assert_eq!(pred.missed_frames, missed);
+            assert_eq!(pred.location, (64.0, 64.0));
+        }
+
+        // and reappears far away from its last known position
+        let (_, pred) = &multi_tracker
+            .track(&frame_with_disc(width, height, (180.0, 150.0), 12.0))
+            .unwrap()[0];
+        assert_eq!(pred.missed_frames, 0);
+        assert!((pred.location.0 - 180.0).abs() < 0.5, "{:?}", pred.location);
+        assert!((pred.location.1 - 150.0).abs() < 0.5, "{:?}", pred.location);
+
+        // after which it is tracked as usual
+        let (_, pred) = &multi_tracker
+            .track(&frame_with_disc(width, height, (183.0, 148.0), 12.0))
+            .unwrap()[0];
+        assert_eq!(pred.missed_frames, 0);
+        assert!((pred.location.0 - 183.0).abs() < 0.5, "{:?}", pred.location);
+    }
+
+    #[test]
+    fn settings_builder() {
+        let settings = MosseTrackerSettings::builder(128, 48).build().unwrap();
+        assert_eq!((settings.window_width, settings.window_height), (64, 48));
+        assert_eq!(settings.learning_rate, DEFAULT_LEARNING_RATE);
+        assert_eq!(settings.psr_exclusion_size, DEFAULT_PSR_EXCLUSION_SIZE);
+        assert_eq!(settings.augmentation, Augmentation::default());
+
+        let invalid = |builder: MosseTrackerSettingsBuilder| builder.build().unwrap_err();
+        let builder = || MosseTrackerSettings::builder(128, 128);
+        assert_eq!(
+            invalid(builder().window_size(1, 16)),
+            MosseError::InvalidWindowSize {
+                width: 1,
+                height: 16
+            }
+        );
+        assert_eq!(
+            invalid(builder().window_size(16, 129)),
+            MosseError::InvalidWindowSize {
+                width: 16,
+                height: 129
+            }
+        );
+        for learning_rate in [0.0, -0.1, 1.5, f32::NAN] {
+            assert!(matches!(
+                invalid(builder().learning_rate(learning_rate)),
+                MosseError::InvalidSetting {
+                    name: "learning rate",
+                    ..
+                }
+            ));
+        }
+        assert!(builder().learning_rate(1.0).build().is_ok());
+        for regularization in [0.0, -0.001] {
+            assert!(matches!(
+                invalid(builder().regularization(regularization)),
+                MosseError::InvalidSetting {
+                    name: "regularization",
+                    ..
+                }
+            ));
+        }
+
+        // the optional parts of the tracker are checked as well
+        let nested = [
+            (
+                builder()
+                    .window_size(16, 16)
+                    .features(Arc::new(Hog { cell_size: 32 })),
+                "feature cell size",
+            ),
+            (
+                builder().features(Arc::new(Hog { cell_size: 0 })),
+                "feature cell size",
+            ),
+            (
+                builder().scale_estimation(ScaleEstimationSettings {
+                    number_of_scales: 0,
+                    ..ScaleEstimationSettings::default()
+                }),
+                "number of scales",
+            ),
+            (
+                builder().scale_estimation(ScaleEstimationSettings {
+                    scale_step: 1.0,
+                    ..ScaleEstimationSettings::default()
+                }),
+                "scale step",
+            ),
+            (
+                builder().kernel(Kernel::Gaussian { sigma: 0.0 }),
+                "kernel sigma",
+            ),
+            (
+                builder().motion_model(MotionModel::ConstantVelocity {
+                    process_noise: 1.0,
+                    measurement_noise: 0.0,
+                }),
+                "measurement noise",
+            ),
+            (
+                builder().motion_model(MotionModel::ConstantAcceleration {
+                    process_noise: -1.0,
+                    measurement_noise: 1.0,
+                }),
+                "process noise",
+            ),
+            (
+                builder().redetection(RedetectionSettings {
+                    growth_factor: 0.5,
+                    full_frame_after: None,
+                }),
+                "growth factor",
+            ),
+        ];
+        for (builder, expected) in nested {
+            assert!(matches!(
+                invalid(builder),
+                MosseError::InvalidSetting { name, .. } if name == expected
+            ));
+        }
+        // a motion model without process noise is fine, as long as the measurements are noisy
+        assert!(builder()
+            .motion_model(MotionModel::ConstantVelocity {
+                process_noise: 0.0,
+                measurement_noise: 1.0,
+            })
+            .build()
+            .is_ok());
+
+        // the former constants can be tuned: a filter trained on the unperturbed window only, with a wider peak
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .target_sigma(2.0)
+            .psr_exclusion_size(7)
+            .augmentation(Augmentation {
+                rotations: vec![],
+                scales: vec![],
+            })
+            .build()
+            .unwrap();
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker
+            .train(
+                &frame_with_blob(width, height, (64, 64), 10, 6),
+                (64.0, 64.0),
+            )
+            .unwrap();
+        let pred = tracker
+            .track_new_frame(&frame_with_blob(width, height, (67, 62), 10, 6))
+            .unwrap();
+        assert!(pred.psr > settings.psr_threshold, "{}", pred.psr);
+        assert!((pred.location.0 - 67.0).abs() < 0.5, "{:?}", pred.location);
+        assert!((pred.location.1 - 62.0).abs() < 0.5, "{:?}", pred.location);
+    }
+
+    #[test]
+    fn uniform_frames() {
+        // a window without any contrast has an empty spectrum, which the regularization keeps from dividing by zero
+        let (width, height) = (64, 64);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(16, 16)
+            .build()
+            .unwrap();
+        for value in [0, 128] {
+            let frame = GrayImage::from_pixel(width, height, Luma([value]));
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker.train(&frame, (32.0, 32.0)).unwrap();
+            assert!(tracker.filter.iter().flatten().all(|h| h.is_finite()));
+            tracker.track_new_frame(&frame).unwrap();
+            tracker.update(&frame);
+            assert!(tracker.filter.iter().flatten().all(|h| h.is_finite()));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_files() {
+        let toml = r#"
+            desperation_level = 5
+
+            [settings]
+            width = 1280
+            height = 720
+            psr_threshold = 8.0
+            features = { type = "hog", cell_size = 4 }
+            kernel = { type = "gaussian", sigma = 0.5 }
+            border_mode = "mirror"
+            redetection = { growth_factor = 2.0 }
+
+            [settings.augmentation]
+            rotations = [0.05, -0.05]
+
+            [dormant_pool]
+            capacity = 8
+            max_age = 300
+
+            [duplicate_suppression]
+            overlap = { type = "iou", min_iou = 0.5 }
+            policy = "merge"
+        "#;
+        let config = TrackerConfig::from_toml_str(toml).unwrap();
+        assert_eq!(config.desperation_level, 5);
+        assert_eq!(config.dormant_pool.unwrap().max_age, Some(300));
+        assert_eq!(
+            config.duplicate_suppression,
+            Some(DuplicateSuppression {
+                overlap: Overlap::Iou { min_iou: 0.5 },
+                policy: DuplicatePolicy::Merge
+            })
+        );
+
+        // settings that are left out take the defaults of the builder
+        let settings = &config.settings;
+        assert_eq!((settings.width, settings.height), (1280, 720));
+        assert_eq!(settings.window_width, DEFAULT_WINDOW_SIZE);
+        assert_eq!(settings.learning_rate, DEFAULT_LEARNING_RATE);
+        assert_eq!(settings.psr_threshold, 8.0);
+        assert_eq!(
+            settings.features.feature_type(),
+            Some(FeatureType::Hog { cell_size: 4 })
+        );
+        assert!(matches!(settings.kernel, Some(Kernel::Gaussian { sigma }) if sigma == 0.5));
+        assert_eq!(settings.border_mode, BorderMode::Mirror);
+        assert!(settings.motion_model.is_none());
+        let redetection = settings.redetection.unwrap();
+        assert_eq!(redetection.growth_factor, 2.0);
+        assert_eq!(redetection.full_frame_after, Some(5));
+        assert_eq!(settings.augmentation.rotations, vec![0.05, -0.05]);
+        assert_eq!(settings.augmentation.scales, Augmentation::default().scales);
+
+        // a configuration survives a round trip through both formats
+        for reloaded in [
+            TrackerConfig::from_toml_str(&config.to_toml_string().unwrap()).unwrap(),
+            TrackerConfig::from_json_str(&config.to_json_string().unwrap()).unwrap(),
+        ] {
+            assert_eq!(
+                format!("{:?}", reloaded.settings),
+                format!("{:?}", config.settings)
+            );
+            assert_eq!(reloaded.desperation_level, config.desperation_level);
+        }
+
+        // invalid settings, unknown settings and custom feature extractors are rejected
+        let invalid = |json: &str| match TrackerConfig::from_json_str(json) {
+            Err(MosseError::InvalidConfig(message)) => message,
+            other => panic!("{:?}", other.map(|c| c.settings)),
+        };
+        let message = invalid(
+            r#"{"desperation_level": 3, "settings": {"width": 64, "height": 64, "learning_rate": 2.0}}"#,
+        );
+        assert!(message.contains("learning rate"), "{}", message);
+        let message = invalid(
+            r#"{"desperation_level": 3, "settings": {"width": 64, "height": 64, "learning_rat": 0.1}}"#,
+        );
+        assert!(message.contains("learning_rat"), "{}", message);
+
+        #[derive(Debug)]
+        struct Custom;
+        impl FeatureExtractor for Custom {
+            fn channels(&self) -> usize {
+                1
+            }
+            fn cell_size(&self) -> u32 {
+                1
+            }
+            fn extract(&self, window: &RgbImage) -> Vec<Vec<f32>> {
+                Intensity.extract(window)
+            }
+        }
+        let settings = MosseTrackerSettings::builder(64, 64)
+            .features(Arc::new(Custom))
+            .build()
+            .unwrap();
+        let config = TrackerConfig {
+            settings,
+            desperation_level: 3,
+            dormant_pool: None,
+            duplicate_suppression: None,
+            history_capacity: 0,
+        };
+        assert!(config.to_json_string().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn checkpoints() {
+        let (width, height) = (256, 256);
+        let disc = |x: f32, y: f32| frame_with_disc(width, height, (x, y), 12.0);
+
+        // a single tracker with all of its optional parts
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .scale_estimation(ScaleEstimationSettings::default())
+            .motion_model(MotionModel::ConstantVelocity {
+                process_noise: 1.0,
+                measurement_noise: 1.0,
+            })
+            .build()
+            .unwrap();
+        let mut tracker = MosseTracker::new(&settings).unwrap();
+        tracker.train(&disc(64.0, 64.0), (64.0, 64.0)).unwrap();
+        for x in [68.0, 72.0] {
+            tracker.track_new_frame(&disc(x, 66.0)).unwrap();
+            tracker.update(&disc(x, 66.0));
+        }
+        let mut restored = [
+            MosseTracker::from_json(&tracker.to_json().unwrap()).unwrap(),
+            MosseTracker::from_bytes(&tracker.to_bytes().unwrap()).unwrap(),
+        ];
+        let expected = tracker.track_new_frame(&disc(76.0, 67.0)).unwrap();
+        for restored in &mut restored {
+            let pred = restored.track_new_frame(&disc(76.0, 67.0)).unwrap();
+            assert_eq!(pred.location, expected.location);
+            assert_eq!(pred.psr, expected.psr);
+            assert_eq!(pred.scale, expected.scale);
+            assert_eq!(
+                pred.motion.unwrap().velocity,
+                expected.motion.as_ref().unwrap().velocity
+            );
+        }
+
+        // the motion model is rebuilt from the settings, so a saved state of another order is rejected
+        let mut json: serde_json::Value =
+            serde_json::from_str(&tracker.to_json().unwrap()).unwrap();
+        json["state"]["kalman_filter"]["x"]["state"] = serde_json::json!([1.0]);
+        assert!(matches!(
+            MosseTracker::from_json(&json.to_string()),
+            Err(MosseError::InvalidCheckpoint(_))
+        ));
+
+        // a multi tracker with a kernelized filter, and a dormant tracker
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .kernel(Kernel::Gaussian { sigma: 0.5 })
+            .build()
+            .unwrap();
+        let dormant_pool = DormantPoolSettings {
+            capacity: 2,
+            max_age: None,
+        };
+        let mut multi_tracker: MultiMosseTracker =
+            MultiMosseTracker::with_dormant_pool(settings, 1, dormant_pool);
+        multi_tracker
+            .add_or_replace_target(1, (64.0, 64.0), &disc(64.0, 64.0))
+            .unwrap();
+        multi_tracker
+            .add_or_replace_target(2, (180.0, 160.0), &disc(180.0, 160.0))
+            .unwrap();
+        multi_tracker.track(&disc(66.0, 62.0)).unwrap();
+        assert_eq!(multi_tracker.size(), 1);
+        assert_eq!(multi_tracker.dormant_targets().len(), 1);
+
+        let bytes = multi_tracker.to_bytes().unwrap();
+        for mut restored in [
+            MultiMosseTracker::from_json(&multi_tracker.to_json().unwrap()).unwrap(),
+            MultiMosseTracker::from_bytes(&bytes).unwrap(),
+        ] {
+            assert_eq!(restored.size(), 1);
+            assert_eq!(restored.dormant_targets(), multi_tracker.dormant_targets());
+            let predictions = restored.track(&disc(68.0, 61.0)).unwrap();
+            let (id, pred) = &predictions[0];
+            assert_eq!(*id, 1);
+            assert!((pred.location.0 - 68.0).abs() < 0.5, "{:?}", pred.location);
+            assert!((pred.location.1 - 61.0).abs() < 0.5, "{:?}", pred.location);
+        }
+
+        // a tracker on a black frame has learned nothing, but JSON has no NaN to hold it, so everything must be finite
+        let black = GrayImage::new(width, height);
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(
+            MosseTrackerSettings::builder(width, height)
+                .build()
+                .unwrap(),
+            3,
+        );
+        multi_tracker.set_history_capacity(4);
+        multi_tracker
+            .add_or_replace_target(1, (64.0, 64.0), &black)
+            .unwrap();
+        multi_tracker.track(&black).unwrap();
+        let json = multi_tracker.to_json().unwrap();
+        let restored = MultiMosseTracker::<u32>::from_json(&json).unwrap();
+        assert!(restored.trackers[0]
+            .tracker
+            .filter
+            .iter()
+            .flatten()
+            .all(|h| h.is_finite()));
+        assert_eq!(restored.trackers[0].tracker.last_psr, 0.0);
+        assert_eq!(restored.to_json().unwrap(), json);
+
+        // checkpoints of other versions or of something else are rejected
+        let mut other_version = bytes.clone();
+        other_version[5] = CHECKPOINT_VERSION as u8 + 1;
+        assert_eq!(
+            MultiMosseTracker::<u32>::from_bytes(&other_version).err(),
+            Some(MosseError::UnsupportedCheckpointVersion(
+                CHECKPOINT_VERSION + 1
+            ))
+        );
+        assert!(matches!(
+            MultiMosseTracker::<u32>::from_bytes(b"PNG").err(),
+            Some(MosseError::InvalidCheckpoint(_))
+        ));
+        let json = tracker.to_json().unwrap().replace(
+            &format!("\"version\":{}", CHECKPOINT_VERSION),
+            "\"version\":0",
+        );
+        assert_eq!(
+            MosseTracker::from_json(&json).err(),
+            Some(MosseError::UnsupportedCheckpointVersion(0))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (width, height) = (64, 64);
+        // the builder would reject the empty window, so it is set afterwards
+        let settings = |window_size| MosseTrackerSettings {
+            window_width: window_size,
+            window_height: window_size,
+            ..MosseTrackerSettings::builder(width, height)
+                .build()
+                .unwrap()
+        };
+
+        assert_eq!(
+            MosseTracker::new(&settings(0)).unwrap_err(),
+            MosseError::InvalidWindowSize {
+                width: 0,
+                height: 0
+            }
+        );
+
+        // frames of the wrong size are rejected
+        let mismatch = Some(MosseError::FrameSizeMismatch {
+            expected: (width, height),
+            actual: (32, 64),
+        });
+        let small_frame = GrayImage::new(32, 64);
+        let mut tracker = MosseTracker::new(&settings(16)).unwrap();
+        assert_eq!(tracker.train(&small_frame, (16.0, 16.0)).err(), mismatch);
+        assert_eq!(tracker.track_new_frame(&small_frame).err(), mismatch);
+
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings(16), 3);
+        let added = multi_tracker.add_or_replace_target(0, (16.0, 16.0), &small_frame);
+        assert_eq!(added.err(), mismatch);
+        assert_eq!(multi_tracker.size(), 0);
+        assert_eq!(multi_tracker.track(&small_frame).err(), mismatch);
+
+        // windows smaller than the area around the peak that is excluded from the PSR still work
+        let frame = frame_with_blob(width, height, (32, 32), 6, 4);
+        let mut tracker = MosseTracker::new(&settings(8)).unwrap();
+        tracker.train(&frame, (32.0, 32.0)).unwrap();
+        tracker.track_new_frame(&frame).unwrap();
+
+        assert_eq!(
+            to_imgbuf(&[0.0; 15], 4, 4).unwrap_err(),
+            MosseError::BufferSizeMismatch {
+                expected: 16,
+                actual: 15
+            }
+        );
+    }
+
+    #[test]
+    fn dormant_pool() {
+        let (width, height) = (256, 256);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(48, 48)
+            .redetection(RedetectionSettings::default())
+            .build()
+            .unwrap();
+        let dormant_pool = DormantPoolSettings {
+            capacity: 4,
+            max_age: Some(10),
+        };
+        let mut multi_tracker: MultiMosseTracker =
+            MultiMosseTracker::with_dormant_pool(settings, 2, dormant_pool);
+        multi_tracker
+            .add_or_replace_target(
+                7,
+                (64.0, 64.0),
+                &frame_with_disc(width, height, (64.0, 64.0), 12.0),
+            )
+            .unwrap();
+
+        // the target disappears, and its tracker becomes dormant
+        let empty = GrayImage::new(width, height);
+        multi_tracker.track(&empty).unwrap();
+        multi_tracker.track(&empty).unwrap();
+        assert_eq!(multi_tracker.size(), 0);
+        assert_eq!(multi_tracker.dormant_targets(), vec![(7, (64.0, 64.0), 2)]);
+        assert!(multi_tracker.track(&empty).unwrap().is_empty());
+
+        // the dormant tracker finds the target when it reappears, and reclaims its ID
+        let disc = frame_with_disc(width, height, (120.0, 100.0), 12.0);
+        let predictions = multi_tracker.track(&disc).unwrap();
+        assert_eq!(predictions.len(), 1);
+        let (id, pred) = &predictions[0];
+        assert_eq!(*id, 7);
+        assert!((pred.location.0 - 120.0).abs() < 0.5, "{:?}", pred.location);
+        assert!((pred.location.1 - 100.0).abs() < 0.5, "{:?}", pred.location);
+        assert_eq!(multi_tracker.size(), 1);
+        assert!(multi_tracker.dormant_targets().is_empty());
+
+        // a new target that is not recognized by the dormant tracker gets the new ID
+        multi_tracker.track(&empty).unwrap();
+        multi_tracker.track(&empty).unwrap();
+        assert_eq!(multi_tracker.dormant_targets().len(), 1);
+        let blob = frame_with_blob(width, height, (60, 180), 30, 10);
+        assert_eq!(
+            multi_tracker
+                .add_or_replace_target(8, (60.0, 180.0), &blob)
+                .unwrap(),
+            8
+        );
+        assert_eq!(multi_tracker.dormant_targets().len(), 1);
+
+        // a target that is recognized keeps the ID the caller gave it, and the dormant tracker keeps waiting
+        let disc = frame_with_disc(width, height, (200.0, 180.0), 12.0);
+        assert_eq!(
+            multi_tracker
+                .add_or_replace_target(9, (200.0, 180.0), &disc)
+                .unwrap(),
+            9
+        );
+        assert!(multi_tracker.get_target(9).is_some());
+        assert_eq!(multi_tracker.dormant_targets().len(), 1);
+        multi_tracker.remove_target(9).unwrap();
+
+        // but a target that is added under an allocated ID is tracked under the original ID
+        assert_eq!(multi_tracker.add_target((200.0, 180.0), &disc).unwrap(), 7);
+        assert!(multi_tracker.dormant_targets().is_empty());
+        assert_eq!(multi_tracker.size(), 2);
+        assert_eq!(multi_tracker.add_target((60.0, 180.0), &blob).unwrap(), 10);
+        multi_tracker.remove_target(10).unwrap();
+
+        // dormant trackers are discarded when they get too old
+        for _ in 0..13 {
+            multi_tracker.track(&empty).unwrap();
+        }
+        assert_eq!(multi_tracker.size(), 0);
+        assert!(multi_tracker.dormant_targets().is_empty());
+    }
+
+    #[test]
+    fn lifecycle() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 2);
+        let frame = frame_with_blob(width, height, (40, 40), 12, 12);
+        multi_tracker
+            .add_or_replace_target(1, (40.0, 40.0), &frame)
+            .unwrap();
+        multi_tracker
+            .add_or_replace_target(2, (90.0, 90.0), &frame)
+            .unwrap();
+
+        let ids: Vec<u32> = multi_tracker.targets().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        let target = multi_tracker.get_target(1).unwrap();
+        assert_eq!(target.location, (40.0, 40.0));
+        assert_eq!(target.missed_frames, 0);
+        assert!(!target.paused);
+        assert!(multi_tracker.get_target(3).is_none());
+
+        // a paused target is neither tracked nor expired
+        multi_tracker.pause_target(1).unwrap();
+        let empty = GrayImage::new(width, height);
+        for _ in 0..3 {
+            let predictions = multi_tracker.track(&empty).unwrap();
+            assert!(predictions.iter().all(|(id, _)| *id != 1));
+        }
+        assert_eq!(multi_tracker.size(), 1);
+        let target = multi_tracker.get_target(1).unwrap();
+        assert!(target.paused);
+        assert_eq!(target.missed_frames, 0);
+
+        // and picks up where it left off when resumed
+        multi_tracker.resume_target(1).unwrap();
+        let predictions = multi_tracker.track(&frame).unwrap();
+        assert_eq!(predictions.len(), 1);
+        let (id, pred) = &predictions[0];
+        assert_eq!(*id, 1);
+        assert!((pred.location.0 - 40.0).abs() < 0.5, "{:?}", pred.location);
+        assert_eq!(multi_tracker.get_target(1).unwrap().missed_frames, 0);
+
+        multi_tracker.remove_target(1).unwrap();
+        assert_eq!(multi_tracker.size(), 0);
+        assert_eq!(
+            multi_tracker.remove_target(1).err(),
+            Some(MosseError::UnknownTarget("1".to_string()))
+        );
+        assert_eq!(
+            multi_tracker.pause_target(2).err(),
+            Some(MosseError::UnknownTarget("2".to_string()))
+        );
+
+        // a target that was moving and being searched for when it was paused starts over from where it was last seen
+        let (width, height) = (256, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .motion_model(MotionModel::ConstantVelocity {
+                process_noise: 1.0,
+                measurement_noise: 1.0,
+            })
+            .redetection(RedetectionSettings::default())
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 5);
+        let disc = |x: f32| frame_with_disc(width, height, (x, 64.0), 10.0);
+        multi_tracker
+            .add_or_replace_target(1, (40.0, 64.0), &disc(40.0))
+            .unwrap();
+        for x in [46.0, 52.0, 58.0, 64.0, 70.0] {
+            multi_tracker.track(&disc(x)).unwrap();
+        }
+        let empty = GrayImage::new(width, height);
+        multi_tracker.track(&empty).unwrap();
+        multi_tracker.track(&empty).unwrap();
+        multi_tracker.pause_target(1).unwrap();
+        let location = multi_tracker.get_target(1).unwrap().location;
+        for _ in 0..5 {
+            multi_tracker.track(&empty).unwrap();
+        }
+
+        multi_tracker.resume_target(1).unwrap();
+        let tracker = &multi_tracker.trackers[0].tracker;
+        assert_eq!(tracker.missed_frames, 0);
+        assert_eq!(tracker.search_region(), (32, 32, location));
+        assert_eq!(tracker.prediction().motion.unwrap().velocity, (0.0, 0.0));
+        let (_, pred) = &multi_tracker.track(&disc(location.0.round())).unwrap()[0];
+        assert_eq!(pred.missed_frames, 0);
+        assert!(
+            (pred.location.0 - location.0.round()).abs() < 0.5,
+            "{:?}",
+            pred.location
+        );
+    }
+
+    #[test]
+    fn events() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        multi_tracker.set_event_handler(move |event| sink.lock().unwrap().push(*event));
+        let take_events = || std::mem::take(&mut *events.lock().unwrap());
+
+        let frame = frame_with_blob(width, height, (40, 40), 12, 12);
+        multi_tracker
+            .add_or_replace_target(1, (40.0, 40.0), &frame)
+            .unwrap();
+        multi_tracker
+            .add_or_replace_target(2, (90.0, 90.0), &frame)
+            .unwrap();
+        assert!(take_events().is_empty());
+        multi_tracker
+            .add_or_replace_target(2, (90.0, 90.0), &frame)
+            .unwrap();
+        assert_eq!(
+            take_events(),
+            vec![TrackerEvent::TargetReplaced {
+                id: 2,
+                frame: 0,
+                location: (90.0, 90.0)
+            }]
+        );
+
+        multi_tracker.remove_target(2).unwrap();
+        assert_eq!(
+            take_events(),
+            vec![TrackerEvent::TargetRemoved {
+                id: 2,
+                frame: 0,
+                reason: RemovalReason::Removed
+            }]
+        );
+
+        // the blob disappears for a frame, and it takes the tracker a frame to find it again
+        let empty = GrayImage::new(width, height);
+        multi_tracker.track(&frame).unwrap();
+        multi_tracker.track(&empty).unwrap();
+        assert!(matches!(
+            take_events()[..],
+            [TrackerEvent::TargetLost {
+                id: 1,
+                frame: 2,
+                ..
+            }]
+        ));
+        multi_tracker.track(&frame).unwrap();
+        assert!(take_events().is_empty());
+        multi_tracker.track(&frame).unwrap();
+        match take_events()[..] {
+            [TrackerEvent::TargetRecovered {
+                id: 1,
+                frame: 4,
+                location,
+            }] => assert!((location.0 - 40.0).abs() < 0.5, "{:?}", location),
+            ref events => panic!("unexpected events {:?}", events),
+        }
+
+        // the blob disappears for good: the target is lost once, and removed when its tracker expires
+        for _ in 0..3 {
+            multi_tracker.track(&empty).unwrap();
+        }
+        let events = take_events();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[0],
+            TrackerEvent::TargetLost {
+                id: 1,
+                frame: 5,
+                ..
+            }
+        ));
+        assert_eq!(
+            events[1],
+            TrackerEvent::TargetRemoved {
+                id: 1,
+                frame: 7,
+                reason: RemovalReason::Expired
+            }
+        );
+    }
+
+    #[test]
+    fn per_target_settings() {
+        let (width, height) = (160, 160);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(16, 16)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+
+        // a small and a large target, the large one with a window that fits it
+        let mut frame = frame_with_blob(width, height, (40, 40), 8, 8);
+        let large = frame_with_blob(width, height, (110, 110), 36, 36);
+        for (x, y) in (80..140).flat_map(|x| (80..140).map(move |y| (x, y))) {
+            frame.put_pixel(x, y, *large.get_pixel(x, y));
+        }
+        let large_settings = MosseTrackerSettings {
+            window_width: 48,
+            window_height: 48,
+            learning_rate: 0.1,
+            ..multi_tracker.settings().clone()
+        };
+        multi_tracker
+            .add_or_replace_target(1, (40.0, 40.0), &frame)
+            .unwrap();
+        multi_tracker
+            .add_or_replace_target_with_settings(2, (110.0, 110.0), &frame, &large_settings)
+            .unwrap();
+        let windows: Vec<(u32, u32)> = multi_tracker.targets().map(|t| t.window_size).collect();
+        assert_eq!(windows, vec![(16, 16), (48, 48)]);
+
+        let predictions = multi_tracker.track(&frame).unwrap();
+        assert_eq!(predictions.len(), 2);
+        for ((_, pred), expected) in predictions.iter().zip([40.0, 110.0]) {
+            assert!(pred.psr > 7.0, "{}", pred.psr);
+            assert!(
+                (pred.location.0 - expected).abs() < 0.5,
+                "{:?}",
+                pred.location
+            );
+        }
+
+        // the settings of a target must be for frames of the same size
+        let other_frame_size = MosseTrackerSettings::builder(width, 100).build().unwrap();
+        assert!(matches!(
+            multi_tracker.add_or_replace_target_with_settings(
+                3,
+                (50.0, 50.0),
+                &frame,
+                &other_frame_size
+            ),
+            Err(MosseError::InvalidSetting { .. })
+        ));
+        assert_eq!(multi_tracker.size(), 2);
+
+        // a target that is recognized by a dormant tracker keeps its ID, but is tracked with the settings it was
+        // added with
+        let dormant_pool = DormantPoolSettings {
+            capacity: 1,
+            max_age: None,
+        };
+        let mut multi_tracker: MultiMosseTracker =
+            MultiMosseTracker::with_dormant_pool(multi_tracker.settings().clone(), 1, dormant_pool);
+        let disc = frame_with_disc(width, height, (80.0, 80.0), 8.0);
+        multi_tracker
+            .add_or_replace_target(1, (80.0, 80.0), &disc)
+            .unwrap();
+        multi_tracker.track(&GrayImage::new(width, height)).unwrap();
+        assert_eq!(multi_tracker.dormant_targets().len(), 1);
+        let id = multi_tracker
+            .add_target_with_settings((80.0, 80.0), &disc, &large_settings)
+            .unwrap();
+        assert_eq!(id, 1);
+        assert!(multi_tracker.dormant_targets().is_empty());
+        let target = multi_tracker.get_target(1).unwrap();
+        assert_eq!(target.window_size, (48, 48));
+    }
+
+    #[test]
+    fn identifier_allocation() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let frame = frame_with_blob(width, height, (64, 64), 12, 12);
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings.clone(), 3);
+        assert_eq!(multi_tracker.add_target((30.0, 30.0), &frame).unwrap(), 0);
+        assert_eq!(multi_tracker.add_target((64.0, 64.0), &frame).unwrap(), 1);
+
+        // allocated IDs skip past the IDs picked by the caller, and are not reused after their target is removed
+        multi_tracker
+            .add_or_replace_target(10, (90.0, 90.0), &frame)
+            .unwrap();
+        assert_eq!(multi_tracker.add_target((30.0, 90.0), &frame).unwrap(), 11);
+        multi_tracker.remove_target(11).unwrap();
+        assert_eq!(multi_tracker.add_target((30.0, 90.0), &frame).unwrap(), 12);
+        multi_tracker
+            .add_or_replace_target(4, (90.0, 30.0), &frame)
+            .unwrap();
+        assert_eq!(multi_tracker.add_target((50.0, 50.0), &frame).unwrap(), 13);
+        assert_eq!(multi_tracker.size(), 6);
+
+        multi_tracker
+            .add_or_replace_target(u32::MAX, (80.0, 80.0), &frame)
+            .unwrap();
+        assert_eq!(
+            multi_tracker.add_target((80.0, 50.0), &frame).err(),
+            Some(MosseError::IdentifiersExhausted)
+        );
+
+        // other ID types can be allocated too
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+        struct Label(char);
+        impl SequentialId for Label {
+            fn first() -> Self {
+                Label('a')
+            }
+            fn next(&self) -> Option<Self> {
+                char::from_u32(self.0 as u32 + 1).map(Label)
+            }
+        }
+        let config = TrackerConfig {
+            settings: settings.clone(),
+            desperation_level: 3,
+            dormant_pool: None,
+            duplicate_suppression: None,
+            history_capacity: 0,
+        };
+        let mut multi_tracker = MultiMosseTracker::<Label>::from_config(config);
+        multi_tracker
+            .add_or_replace_target(Label('x'), (64.0, 64.0), &frame)
+            .unwrap();
+        assert_eq!(
+            multi_tracker.add_target((30.0, 30.0), &frame).unwrap(),
+            Label('y')
+        );
+        let predictions = multi_tracker.track(&frame).unwrap();
+        assert_eq!(predictions[0].0, Label('x'));
+        assert!(multi_tracker.get_target(Label('y')).is_some());
+
+        // and any ID type can be picked by the caller, like UUIDs
+        let mut multi_tracker = MultiMosseTracker::<[u8; 16]>::new(settings, 3);
+        multi_tracker
+            .add_or_replace_target([7; 16], (64.0, 64.0), &frame)
+            .unwrap();
+        assert_eq!(multi_tracker.track(&frame).unwrap()[0].0, [7; 16]);
+    }
+
+    #[test]
+    fn duplicate_suppression() {
+        let (width, height) = (160, 160);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let mut frame = frame_with_blob(width, height, (50, 50), 12, 12);
+        let other = frame_with_blob(width, height, (110, 110), 12, 12);
+        for (x, y) in (90..130).flat_map(|x| (90..130).map(move |y| (x, y))) {
+            frame.put_pixel(x, y, *other.get_pixel(x, y));
+        }
+
+        let policies = [
+            DuplicatePolicy::KeepOlder,
+            DuplicatePolicy::DropLowerPsr,
+            DuplicatePolicy::Merge,
+        ];
+        let overlaps = [
+            Overlap::CenterDistance { max_distance: 4.0 },
+            Overlap::Iou { min_iou: 0.7 },
+        ];
+        for (policy, overlap) in policies.into_iter().zip(overlaps.into_iter().cycle()) {
+            let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings.clone(), 3);
+            multi_tracker.set_duplicate_suppression(Some(DuplicateSuppression { overlap, policy }));
+            let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let sink = events.clone();
+            multi_tracker.set_event_handler(move |event| sink.lock().unwrap().push(*event));
+
+            // two trackers on the same blob, one slightly off, and one on another blob
+            multi_tracker
+                .add_or_replace_target(1, (50.0, 50.0), &frame)
+                .unwrap();
+            multi_tracker
+                .add_or_replace_target(2, (110.0, 110.0), &frame)
+                .unwrap();
+            multi_tracker
+                .add_or_replace_target(3, (52.0, 51.0), &frame)
+                .unwrap();
+
+            let predictions = multi_tracker.track(&frame).unwrap();
+            let ids: Vec<u32> = predictions.iter().map(|(id, _)| *id).collect();
+            let events = events.lock().unwrap();
+            assert_eq!(events.len(), 2, "{:?}", policy);
+            let TrackerEvent::DuplicateSuppressed {
+                id: dropped,
+                duplicate_of: kept,
+                frame: 1,
+            } = events[0]
+            else {
+                panic!("unexpected event {:?}", events[0]);
+            };
+            assert_eq!(
+                events[1],
+                TrackerEvent::TargetRemoved {
+                    id: dropped,
+                    frame: 1,
+                    reason: RemovalReason::Duplicate
+                }
+            );
+            assert_eq!(ids.len(), 2);
+            assert!(ids.contains(&2) && ids.contains(&kept) && !ids.contains(&dropped));
+            assert_eq!(multi_tracker.size(), 2);
+            assert!(multi_tracker.get_target(dropped).is_none());
+
+            let kept_location = multi_tracker.get_target(kept).unwrap().location;
+            assert!((kept_location.0 - 50.0).abs() < 0.5, "{:?}", kept_location);
+            match policy {
+                DuplicatePolicy::KeepOlder | DuplicatePolicy::Merge => assert_eq!(kept, 1),
+                DuplicatePolicy::DropLowerPsr => {
+                    let psr = |id| predictions.iter().find(|(other, _)| *other == id);
+                    assert!(psr(dropped).is_none());
+                    assert!(psr(kept).unwrap().1.psr > 7.0);
+                }
+            }
+        }
+
+        // merged trackers average what they learned, if their filters fit together
+        let mut trackers = [(50.0, 50.0), (52.0, 51.0)].map(|location| {
+            let mut tracker = MosseTracker::new(&settings).unwrap();
+            tracker.train(&frame, location).unwrap();
+            tracker
+        });
+        let expected: Vec<Complex<f32>> = trackers[0]
+            .last_bottom
+            .iter()
+            .zip(&trackers[1].last_bottom)
+            .map(|(a, b)| (a + b) / 2.0)
+            .collect();
+        let [merged, other] = &mut trackers;
+        assert!(merged.merge(other));
+        assert_eq!(merged.last_bottom, expected);
+        let smaller = MosseTrackerSettings::builder(width, height)
+            .window_size(24, 24)
+            .build()
+            .unwrap();
+        let mut smaller = MosseTracker::new(&smaller).unwrap();
+        smaller.train(&frame, (50.0, 50.0)).unwrap();
+        assert!(!merged.merge(&smaller));
+        assert_eq!(merged.last_bottom, expected);
+
+        // an older target that was missed does not win over a newer one that was not, as it is about to expire
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .psr_threshold(20.0)
+            .redetection(RedetectionSettings::default())
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+        multi_tracker.set_duplicate_suppression(Some(DuplicateSuppression {
+            overlap: Overlap::CenterDistance { max_distance: 4.0 },
+            policy: DuplicatePolicy::KeepOlder,
+        }));
+        multi_tracker
+            .add_or_replace_target(1, (50.0, 50.0), &frame)
+            .unwrap();
+        multi_tracker.track(&GrayImage::new(width, height)).unwrap();
+        assert_eq!(multi_tracker.get_target(1).unwrap().missed_frames, 1);
+        let disc = frame_with_disc(width, height, (51.0, 50.0), 8.0);
+        multi_tracker
+            .add_or_replace_target(2, (51.0, 50.0), &disc)
+            .unwrap();
+        let predictions = multi_tracker.track(&disc).unwrap();
+        assert_eq!(predictions.len(), 1);
+        assert_eq!(predictions[0].0, 2);
+        assert!(multi_tracker.get_target(1).is_none());
+    }
+
+    #[test]
+    fn association() {
+        let (width, height) = (160, 160);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let mut frame = frame_with_blob(width, height, (40, 40), 12, 12);
+        let other = frame_with_blob(width, height, (110, 110), 12, 12);
+        for (x, y) in (90..130).flat_map(|x| (90..130).map(move |y| (x, y))) {
+            frame.put_pixel(x, y, *other.get_pixel(x, y));
+        }
+        let detection = |x: f32, y: f32| Detection {
+            left: x - 6.0,
+            top: y - 6.0,
+            width: 12.0,
+            height: 12.0,
+        };
+
+        for overlap in [
+            Overlap::Iou { min_iou: 0.3 },
+            Overlap::CenterDistance { max_distance: 5.0 },
+        ] {
+            let association = AssociationSettings {
+                overlap,
+                max_misses: 1,
+            };
+            let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings.clone(), 3);
+            multi_tracker.add_target((40.0, 40.0), &frame).unwrap();
+
+            // the known target is matched, and the other detection becomes a new target
+            multi_tracker.track(&frame).unwrap();
+            let detections = [detection(110.0, 110.0), detection(41.0, 40.0)];
+            let associations = multi_tracker
+                .associate(&frame, &detections, &association)
+                .unwrap();
+            assert_eq!(associations.matched, vec![(1, 0)]);
+            assert_eq!(associations.spawned, vec![(0, 1)]);
+            assert!(associations.aged_out.is_empty());
+            // matched targets move to the center of their detection
+            assert_eq!(multi_tracker.get_target(0).unwrap().location, (41.0, 40.0));
+            assert_eq!(
+                multi_tracker.get_target(1).unwrap().location,
+                (110.0, 110.0)
+            );
+
+            // both are matched while they are detected
+            let predictions = multi_tracker.track(&frame).unwrap();
+            assert_eq!(predictions.len(), 2);
+            let associations = multi_tracker
+                .associate(
+                    &frame,
+                    &[detection(40.0, 40.0), detection(110.0, 110.0)],
+                    &association,
+                )
+                .unwrap();
+            assert_eq!(associations.matched, vec![(0, 0), (1, 1)]);
+            assert!(associations.spawned.is_empty());
+
+            // a target that is not detected is removed once it goes unmatched for too long
+            for misses in 1..=2 {
+                multi_tracker.track(&frame).unwrap();
+                let associations = multi_tracker
+                    .associate(&frame, &[detection(40.0, 40.0)], &association)
+                    .unwrap();
+                assert_eq!(associations.matched, vec![(0, 0)]);
+                let aged_out: Vec<u32> = if misses > 1 { vec![1] } else { vec![] };
+                assert_eq!(associations.aged_out, aged_out);
+            }
+            assert_eq!(multi_tracker.size(), 1);
+            assert!(multi_tracker.get_target(1).is_none());
+        }
+
+        // a detection where the tracker found its target replaces the update and the motion measurement of the
+        // tracker, instead of adding to them
+        let association = AssociationSettings {
+            overlap: Overlap::CenterDistance {
+                max_distance: 1000.0,
+            },
+            max_misses: 1,
+        };
+        let settings = MosseTrackerSettings {
+            motion_model: Some(MotionModel::ConstantVelocity {
+                process_noise: 1.0,
+                measurement_noise: 1.0,
+            }),
+            ..settings
+        };
+        let mut tracked: MultiMosseTracker = MultiMosseTracker::new(settings.clone(), 3);
+        let mut detected: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+        for multi_tracker in [&mut tracked, &mut detected] {
+            multi_tracker.add_target((40.0, 40.0), &frame).unwrap();
+            multi_tracker.track(&frame).unwrap();
+        }
+        let (x, y) = detected.get_target(0).unwrap().location;
+        detected
+            .associate(&frame, &[detection(x, y)], &association)
+            .unwrap();
+        let filters =
+            |multi_tracker: &MultiMosseTracker| multi_tracker.trackers[0].tracker.filter.clone();
+        for (tracked, detected) in filters(&tracked).iter().zip(&filters(&detected)) {
+            for (a, b) in tracked.iter().zip(detected) {
+                assert!((a - b).norm() <= 1e-4 * a.norm().max(1.0), "{} {}", a, b);
+            }
+        }
+        let covariance = |multi_tracker: &MultiMosseTracker| {
+            let kalman_filter = multi_tracker.trackers[0].tracker.kalman_filter.as_ref();
+            kalman_filter.unwrap().estimate().covariance.0
+        };
+        for (tracked, detected) in covariance(&tracked).iter().zip(&covariance(&detected)) {
+            for (a, b) in tracked.iter().zip(detected) {
+                assert!((a - b).abs() <= 1e-4 * a.abs().max(1.0), "{} {}", a, b);
+            }
+        }
+
+        // a lost target that is matched is recovered
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        detected.set_event_handler(move |event| sink.lock().unwrap().push(*event));
+        detected.track(&GrayImage::new(width, height)).unwrap();
+        detected
+            .associate(&frame, &[detection(40.0, 40.0)], &association)
+            .unwrap();
+        assert!(matches!(
+            events.lock().unwrap()[..],
+            [
+                TrackerEvent::TargetLost {
+                    id: 0,
+                    frame: 2,
+                    ..
+                },
+                TrackerEvent::TargetRecovered {
+                    id: 0,
+                    frame: 2,
+                    location: (40.0, 40.0),
+                }
+            ]
+        ));
+    }
+
+    #[test]
+    fn history() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 32)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+        multi_tracker.set_history_capacity(4);
+        let frame = |x| frame_with_blob(width, height, (x, 64), 12, 12);
+        multi_tracker
+            .add_or_replace_target(1, (40.0, 64.0), &frame(40))
+            .unwrap();
+        let trajectory = multi_tracker.trajectory(1).unwrap();
+        assert_eq!(trajectory.len(), 1);
+        assert_eq!(trajectory[0].frame, 0);
+        assert_eq!(trajectory[0].location, (40.0, 64.0));
+        assert_eq!(trajectory[0].status, TrackStatus::Detected);
+
+        // the blob moves 2 pixels to the right on every frame, and the history keeps the last 4 points
+        for step in 1..=5 {
+            multi_tracker.track(&frame(40 + 2 * step)).unwrap();
+        }
+        let trajectory = multi_tracker.trajectory(1).unwrap();
+        let frames: Vec<u64> = trajectory.iter().map(|point| point.frame).collect();
+        assert_eq!(frames, vec![2, 3, 4, 5]);
+        for point in &trajectory {
+            assert_eq!(point.status, TrackStatus::Tracked);
+            assert!(point.psr > 7.0, "{}", point.psr);
+            let x = 40.0 + 2.0 * point.frame as f32;
+            assert!((point.location.0 - x).abs() < 0.5, "{:?}", point.location);
+        }
+        assert_eq!(multi_tracker.last_points(1, 2).unwrap(), trajectory[2..]);
+        assert_eq!(multi_tracker.last_points(1, 10).unwrap(), trajectory);
+        let path_length = multi_tracker.path_length(1).unwrap();
+        assert!((path_length - 6.0).abs() < 1.0, "{}", path_length);
+
+        #[cfg(feature = "serde")]
+        {
+            let restored =
+                MultiMosseTracker::<u32>::from_bytes(&multi_tracker.to_bytes().unwrap()).unwrap();
+            assert_eq!(restored.trajectory(1), Some(trajectory));
+        }
+
+        // a replaced target starts over, and a removed target has no history
+        multi_tracker
+            .add_or_replace_target(1, (60.0, 64.0), &frame(50))
+            .unwrap();
+        assert_eq!(multi_tracker.trajectory(1).unwrap().len(), 1);
+        assert_eq!(multi_tracker.path_length(1), Some(0.0));
+        multi_tracker.remove_target(1).unwrap();
+        assert_eq!(multi_tracker.trajectory(1), None);
+        assert_eq!(multi_tracker.last_points(1, 2), None);
+    }
+
+    #[test]
+    fn mot_files() {
+        let (width, height) = (128, 128);
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(32, 24)
+            .build()
+            .unwrap();
+        let mut multi_tracker: MultiMosseTracker = MultiMosseTracker::new(settings, 3);
+        let frame = frame_with_blob(width, height, (40, 50), 12, 12);
+        multi_tracker
+            .add_or_replace_target(3, (40.0, 50.0), &frame)
+            .unwrap();
+
+        let mut writer = MotWriter::new(Vec::new(), (32, 24));
+        for frame_number in 1..=2 {
+            let predictions = multi_tracker.track(&frame).unwrap();
+            writer.write_frame(frame_number, &predictions).unwrap();
+        }
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("1,3,"), "{}", lines[0]);
+        assert!(lines[1].ends_with(",-1,-1,-1"), "{}", lines[1]);
+
+        // the boxes are written in the 1-based pixel coordinates of MOTChallenge
+        let fields: Vec<f32> = lines[1].split(',').map(|f| f.parse().unwrap()).collect();
+        assert!((fields[2] - 25.0).abs() < 0.5, "{}", lines[1]);
+        assert!((fields[3] - 39.0).abs() < 0.5, "{}", lines[1]);
+
+        // the results can be read back
+        let records = read_mot(text.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        let record = records[1];
+        assert_eq!((record.frame, record.id), (2, Some(3)));
+        assert_eq!((record.width, record.height), (32.0, 24.0));
+        assert!((record.left - 24.0).abs() < 0.5, "{:?}", record);
+        assert!((record.top - 38.0).abs() < 0.5, "{:?}", record);
+        assert!(record.confidence > 7.0, "{:?}", record);
+        assert_eq!(
+            record.detection().center(),
+            (record.left + 16.0, record.top + 12.0)
+        );
+
+        // ground truth files have other columns after the sixth, or none at all, and pixels that count from 1
+        let ground_truth = "1,1,912,484,97,109,0,7,1\n\n2.0,1.0,912.5,484,97,109\n";
+        let records = read_mot(ground_truth.as_bytes()).unwrap();
+        assert_eq!(
+            records,
+            vec![
+                MotRecord {
+                    frame: 1,
+                    id: Some(1),
+                    left: 911.0,
+                    top: 483.0,
+                    width: 97.0,
+                    height: 109.0,
+                    confidence: 0.0,
+                },
+                MotRecord {
+                    frame: 2,
+                    id: Some(1),
+                    left: 911.5,
+                    top: 483.0,
+                    width: 97.0,
+                    height: 109.0,
+                    confidence: 1.0,
+                },
+            ]
+        );
+        assert!(matches!(
+            read_mot("1,1,912,484\n".as_bytes()),
+            Err(MosseError::InvalidMotLine { line: 1, .. })
+        ));
+        assert!(matches!(
+            read_mot("1,1,912,484,97,109\n1,-2,912,484,97,109\n".as_bytes()),
+            Err(MosseError::InvalidMotLine { line: 2, .. })
+        ));
+
+        // the detections of det.txt files have no ID
+        let records = read_mot("1,-1,912,484,97,109,0.98,-1,-1,-1\n".as_bytes()).unwrap();
+        assert_eq!(records[0].id, None);
+        assert_eq!(records[0].confidence, 0.98);
+        assert_eq!(
+            records[0].detection(),
+            Detection {
+                left: 911.0,
+                top: 483.0,
+                width: 97.0,
+                height: 109.0,
+            }
         );
     }
 }
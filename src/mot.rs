@@ -0,0 +1,166 @@
+// Tracking results and ground truth in the text format of the MOTChallenge benchmarks.
+//
+// Every line holds one box of one target on one frame: `frame,id,left,top,width,height,confidence,x,y,z`, with
+// frames numbered from 1 and the last three (world coordinates) set to -1 for 2D tracking. Pixels are numbered from
+// 1 as well, so the boxes are moved by a pixel from and to the coordinates of the trackers, which count from 0,
+// when they are read and written. The writer reports the tracking window of every prediction, grown or shrunk with
+// the target, as its box, and the PSR as the confidence.
+// Ground truth files have the same first six columns; the columns after them differ between the benchmarks, so
+// only the seventh is read, as the confidence (or the flag that marks the boxes to consider). Detection files
+// (det.txt) have the same format, with an ID of -1, as detections do not belong to a target yet.
+
+use crate::{Detection, MosseError, Prediction};
+use std::io::{BufRead, Write};
+
+// one line of a MOTChallenge file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotRecord {
+    pub frame: u64,
+    // None for an ID of -1, like in detection files
+    pub id: Option<u64>,
+    // in the pixel coordinates of the trackers, one less than in the file
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+    // 1 if the file has no seventh column
+    pub confidence: f32,
+}
+
+impl MotRecord {
+    pub fn detection(&self) -> Detection {
+        Detection {
+            left: self.left,
+            top: self.top,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MotWriter<W> {
+    writer: W,
+    // the size of the box of a target at scale 1, unless given per prediction
+    window_size: (u32, u32),
+}
+
+impl<W: Write> MotWriter<W> {
+    pub fn new(writer: W, window_size: (u32, u32)) -> MotWriter<W> {
+        MotWriter {
+            writer,
+            window_size,
+        }
+    }
+
+    // Write the predictions of a frame, as returned by MultiMosseTracker::track(). Frames are numbered from 1, like
+    // the frames of the multi-tracker.
+    pub fn write_frame<Id: Copy + Into<u64>>(
+        &mut self,
+        frame: u64,
+        predictions: &[(Id, Prediction)],
+    ) -> Result<(), MosseError> {
+        for (id, prediction) in predictions {
+            self.write_prediction(frame, *id, prediction, self.window_size)?;
+        }
+        Ok(())
+    }
+
+    // Write a single prediction, for a target with a tracking window of the given size, such as the window_size of
+    // MultiMosseTracker::get_target() for targets that were added with their own settings. The box is written in the
+    // 1-based pixel coordinates of MOTChallenge, one more than the location of the prediction.
+    pub fn write_prediction<Id: Into<u64>>(
+        &mut self,
+        frame: u64,
+        id: Id,
+        prediction: &Prediction,
+        window_size: (u32, u32),
+    ) -> Result<(), MosseError> {
+        let width = window_size.0 as f32 * prediction.scale;
+        let height = window_size.1 as f32 * prediction.scale;
+        let (x, y) = prediction.location;
+        writeln!(
+            self.writer,
+            "{},{},{:.2},{:.2},{:.2},{:.2},{:.2},-1,-1,-1",
+            frame,
+            id.into(),
+            x - width / 2.0 + 1.0,
+            y - height / 2.0 + 1.0,
+            width,
+            height,
+            prediction.psr
+        )
+        .map_err(|e| MosseError::Io(e.to_string()))
+    }
+
+    pub fn flush(&mut self) -> Result<(), MosseError> {
+        self.writer
+            .flush()
+            .map_err(|e| MosseError::Io(e.to_string()))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// Read the records of a MOTChallenge file, like a ground truth file, in the order of the file. Empty lines are skipped.
+// The boxes are converted from the 1-based pixel coordinates of the file to those of the trackers, which are one less.
+pub fn read_mot<R: BufRead>(reader: R) -> Result<Vec<MotRecord>, MosseError> {
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| MosseError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(
+            parse_record(&line).map_err(|reason| MosseError::InvalidMotLine {
+                line: index + 1,
+                reason,
+            })?,
+        );
+    }
+    Ok(records)
+}
+
+fn parse_record(line: &str) -> Result<MotRecord, String> {
+    let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if fields.len() < 6 {
+        return Err(format!("expected at least 6 columns, got {}", fields.len()));
+    }
+    let number = |column: usize| {
+        fields[column].parse::<f32>().map_err(|_| {
+            format!(
+                "column {} is not a number: {:?}",
+                column + 1,
+                fields[column]
+            )
+        })
+    };
+    // frame numbers and IDs are written as floats by some tools
+    let integer = |column: usize| match fields[column].parse::<u64>() {
+        Ok(value) => Ok(value),
+        Err(_) => match fields[column].parse::<f64>() {
+            Ok(value) if value >= 0.0 && value.fract() == 0.0 => Ok(value as u64),
+            _ => Err(format!(
+                "column {} is not a non-negative integer: {:?}",
+                column + 1,
+                fields[column]
+            )),
+        },
+    };
+    let id = if fields[1].parse::<f64>() == Ok(-1.0) {
+        None
+    } else {
+        Some(integer(1)?)
+    };
+    Ok(MotRecord {
+        frame: integer(0)?,
+        id,
+        left: number(2)? - 1.0,
+        top: number(3)? - 1.0,
+        width: number(4)?,
+        height: number(5)?,
+        confidence: if fields.len() > 6 { number(6)? } else { 1.0 },
+    })
+}
@@ -0,0 +1,240 @@
+// A Kalman filter motion model, which predicts where the target will be on the next frame.
+//
+// The tracking window is cropped around the predicted position instead of the last detected one, so targets that
+// move quickly stay within the window. The correlation peak is the measurement that corrects the prediction.
+// The horizontal and vertical axes are modelled independently. Time is measured in frames.
+
+// the variance of the initial velocity (and acceleration) estimate, in (pixels per frame)^2
+const INITIAL_MOTION_VARIANCE: f32 = 100.0;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", rename_all = "snake_case")
+)]
+pub enum MotionModel {
+    // The target moves at a constant velocity, disturbed by random accelerations.
+    ConstantVelocity {
+        // variance of the random accelerations
+        process_noise: f32,
+        // variance of the detected position, in pixels^2
+        measurement_noise: f32,
+    },
+    // The target moves at a constant acceleration, disturbed by random changes in acceleration.
+    ConstantAcceleration {
+        // variance of the random changes in acceleration
+        process_noise: f32,
+        // variance of the detected position, in pixels^2
+        measurement_noise: f32,
+    },
+}
+
+// The state of the motion model, as reported with each prediction.
+#[derive(Debug, Clone)]
+pub struct MotionEstimate {
+    // in pixels per frame
+    pub velocity: (f32, f32),
+    // The covariance matrices of the state of the horizontal and vertical axes.
+    // The state of an axis is its position, velocity and, for the constant acceleration model, acceleration.
+    pub covariance: (Vec<Vec<f32>>, Vec<Vec<f32>>),
+}
+
+// a Kalman filter over the position, velocity and optionally acceleration along a single axis
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct AxisFilter {
+    state: Vec<f32>,
+    covariance: Vec<Vec<f32>>,
+}
+
+impl AxisFilter {
+    fn new(order: usize, position: f32, measurement_noise: f32) -> AxisFilter {
+        let mut state = vec![0.0; order];
+        state[0] = position;
+
+        let covariance = (0..order)
+            .map(|i| {
+                (0..order)
+                    .map(|j| match (i, j) {
+                        (0, 0) => measurement_noise,
+                        (i, j) if i == j => INITIAL_MOTION_VARIANCE,
+                        _ => 0.0,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        AxisFilter { state, covariance }
+    }
+
+    // x = F x, P = F P F' + Q
+    fn predict(&mut self, transition: &[Vec<f32>], process_noise: &[Vec<f32>]) {
+        let order = self.state.len();
+        self.state = (0..order)
+            .map(|i| (0..order).map(|k| transition[i][k] * self.state[k]).sum())
+            .collect();
+
+        let fp: Vec<Vec<f32>> = (0..order)
+            .map(|i| {
+                (0..order)
+                    .map(|j| {
+                        (0..order)
+                            .map(|k| transition[i][k] * self.covariance[k][j])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect();
+        self.covariance = (0..order)
+            .map(|i| {
+                (0..order)
+                    .map(|j| {
+                        (0..order).map(|k| fp[i][k] * transition[j][k]).sum::<f32>()
+                            + process_noise[i][j]
+                    })
+                    .collect()
+            })
+            .collect();
+    }
+
+    // correct the state with a measured position (H = [1, 0, ...])
+    fn correct(&mut self, position: f32, measurement_noise: f32) {
+        let order = self.state.len();
+        let residual = position - self.state[0];
+        let residual_variance = self.covariance[0][0] + measurement_noise;
+        let gain: Vec<f32> = (0..order)
+            .map(|i| self.covariance[i][0] / residual_variance)
+            .collect();
+
+        for (x, k) in self.state.iter_mut().zip(&gain) {
+            *x += k * residual;
+        }
+
+        // P = (I - K H) P
+        let first_row = self.covariance[0].clone();
+        for (row, k) in self.covariance.iter_mut().zip(&gain) {
+            for (p, p0) in row.iter_mut().zip(&first_row) {
+                *p -= k * p0;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct KalmanFilter {
+    measurement_noise: f32,
+    transition: Vec<Vec<f32>>,
+    process_noise: Vec<Vec<f32>>,
+    x: AxisFilter,
+    y: AxisFilter,
+}
+
+// The part of a Kalman filter that cannot be derived from its motion model, which checkpoints save: the state and
+// covariance of both axes
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub(crate) struct KalmanState {
+    x: AxisFilter,
+    y: AxisFilter,
+}
+
+impl KalmanFilter {
+    pub(crate) fn new(model: MotionModel, position: (f32, f32)) -> KalmanFilter {
+        // The random disturbance enters the highest derivative of the state, and is integrated into the others
+        // over a single frame (a piecewise constant white noise model).
+        let (transition, noise_gain, process_noise, measurement_noise) = match model {
+            MotionModel::ConstantVelocity {
+                process_noise,
+                measurement_noise,
+            } => (
+                vec![vec![1.0, 1.0], vec![0.0, 1.0]],
+                vec![0.5, 1.0],
+                process_noise,
+                measurement_noise,
+            ),
+            MotionModel::ConstantAcceleration {
+                process_noise,
+                measurement_noise,
+            } => (
+                vec![
+                    vec![1.0, 1.0, 0.5],
+                    vec![0.0, 1.0, 1.0],
+                    vec![0.0, 0.0, 1.0],
+                ],
+                vec![0.5, 1.0, 1.0],
+                process_noise,
+                measurement_noise,
+            ),
+        };
+
+        let order = transition.len();
+        let process_noise = noise_gain
+            .iter()
+            .map(|gi| {
+                noise_gain
+                    .iter()
+                    .map(|gj| gi * gj * process_noise)
+                    .collect()
+            })
+            .collect();
+
+        KalmanFilter {
+            measurement_noise,
+            transition,
+            process_noise,
+            x: AxisFilter::new(order, position.0, measurement_noise),
+            y: AxisFilter::new(order, position.1, measurement_noise),
+        }
+    }
+
+    // advance the model by a single frame, and return the predicted position
+    pub(crate) fn predict(&mut self) -> (f32, f32) {
+        self.x.predict(&self.transition, &self.process_noise);
+        self.y.predict(&self.transition, &self.process_noise);
+        self.position()
+    }
+
+    // correct the prediction with the detected position
+    pub(crate) fn correct(&mut self, position: (f32, f32)) {
+        self.x.correct(position.0, self.measurement_noise);
+        self.y.correct(position.1, self.measurement_noise);
+    }
+
+    pub(crate) fn position(&self) -> (f32, f32) {
+        (self.x.state[0], self.y.state[0])
+    }
+
+    pub(crate) fn estimate(&self) -> MotionEstimate {
+        MotionEstimate {
+            velocity: (self.x.state[1], self.y.state[1]),
+            covariance: (self.x.covariance.clone(), self.y.covariance.clone()),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn state(&self) -> KalmanState {
+        KalmanState {
+            x: self.x.clone(),
+            y: self.y.clone(),
+        }
+    }
+
+    // A Kalman filter of the motion model in a saved state, or None if the state does not have the order of the model
+    #[cfg(feature = "serde")]
+    pub(crate) fn restore(model: MotionModel, state: KalmanState) -> Option<KalmanFilter> {
+        let mut filter = KalmanFilter::new(model, (0.0, 0.0));
+        let order = filter.transition.len();
+        let fits = |axis: &AxisFilter| {
+            axis.state.len() == order
+                && axis.covariance.len() == order
+                && axis.covariance.iter().all(|row| row.len() == order)
+        };
+        if !fits(&state.x) || !fits(&state.y) {
+            return None;
+        }
+        filter.x = state.x;
+        filter.y = state.y;
+        Some(filter)
+    }
+}
@@ -0,0 +1,327 @@
+// Scale estimation with a separate, one-dimensional scale correlation filter.
+// See Danelljan et al. (2014). Accurate Scale Estimation for Robust Visual Tracking (DSST).
+//
+// The translation filter runs first. At the new target location, we sample patches at a range of scales,
+// resample them to a fixed-size template and stack their features into a matrix with one column per scale.
+// Each feature row is then transformed along the scale dimension, and the filter response peaks at the scale
+// that best matches the learned appearance of the target.
+
+use crate::{log_normalize, scaled_window_crop, BorderMode, Frame};
+use image::imageops::{self, FilterType};
+use rustfft::num_complex::Complex;
+use rustfft::num_traits::Zero;
+use rustfft::{Fft, FftPlanner};
+use std::cmp::Ordering;
+use std::f32;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+// the scale samples are resampled to a template of at most this many pixels before extracting features.
+const SCALE_MODEL_MAX_AREA: f32 = 512.0;
+
+// standard deviation of the desired scale response, relative to the square root of the number of scales.
+const SCALE_SIGMA_FACTOR: f32 = 0.25;
+
+// added to the denominator of the scale filter to avoid dividing by zero (lambda in the DSST paper).
+const SCALE_REGULARIZATION: f32 = 0.01;
+
+// the scaled tracking window may not shrink below this many pixels in either dimension.
+const MIN_SCALED_WINDOW_SIZE: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct ScaleEstimationSettings {
+    // the number of scales that are evaluated on each frame. Should be odd, so the current scale is in the middle.
+    pub number_of_scales: u32,
+    // the ratio between two consecutive scales
+    pub scale_step: f32,
+    // learning rate of the scale filter
+    pub learning_rate: f32,
+}
+
+impl Default for ScaleEstimationSettings {
+    // the parameters used in the DSST paper
+    fn default() -> Self {
+        ScaleEstimationSettings {
+            number_of_scales: 33,
+            scale_step: 1.02,
+            learning_rate: 0.025,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ScaleFilter {
+    // the relative scale of each of the samples, the current scale being in the middle
+    scale_factors: Vec<f32>,
+
+    // a Hann window over the scale samples
+    scale_window: Vec<f32>,
+
+    // the 'target' (G). A one-dimensional Gaussian peak centered on the current scale.
+    target: Vec<Complex<f32>>,
+
+    // the numerator (one row per feature) and the shared denominator of the scale filter
+    pub(crate) top: Vec<Vec<Complex<f32>>>,
+    pub(crate) bottom: Vec<Complex<f32>>,
+
+    // every scale sample is resampled to these dimensions before feature extraction
+    model_width: u32,
+    model_height: u32,
+
+    // bounds on the target scale, so the scaled window does not vanish or outgrow the frame
+    min_scale: f32,
+    max_scale: f32,
+
+    eta: f32,
+
+    // how the parts of scale samples outside of the frame are filled in
+    border_mode: BorderMode,
+
+    // FFT objects for transforms along the scale dimension
+    fft: Arc<dyn Fft<f32>>,
+    inv_fft: Arc<dyn Fft<f32>>,
+}
+
+impl Debug for ScaleFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScaleFilter")
+            .field("scale_factors", &self.scale_factors)
+            .field("model_width", &self.model_width)
+            .field("model_height", &self.model_height)
+            .field("min_scale", &self.min_scale)
+            .field("max_scale", &self.max_scale)
+            .field("eta", &self.eta)
+            .field("border_mode", &self.border_mode)
+            // the filter itself is too large to be of any use in debug output, and the FFT objects don't implement Debug.
+            .finish()
+    }
+}
+
+impl ScaleFilter {
+    pub(crate) fn new(
+        settings: &ScaleEstimationSettings,
+        window_width: u32,
+        window_height: u32,
+        frame_width: u32,
+        frame_height: u32,
+        border_mode: BorderMode,
+    ) -> ScaleFilter {
+        let number_of_scales = settings.number_of_scales.max(1) as usize;
+        let center = (number_of_scales / 2) as f32;
+
+        let scale_factors: Vec<f32> = (0..number_of_scales)
+            .map(|n| settings.scale_step.powf(n as f32 - center))
+            .collect();
+
+        let scale_window: Vec<f32> = if number_of_scales == 1 {
+            vec![1.0]
+        } else {
+            (0..number_of_scales)
+                .map(|n| {
+                    0.5 * (1.0
+                        - ((2.0 * f32::consts::PI * n as f32) / (number_of_scales - 1) as f32)
+                            .cos())
+                })
+                .collect()
+        };
+
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(number_of_scales);
+        let inv_fft = planner.plan_fft_inverse(number_of_scales);
+
+        // build the desired response: a Gaussian peak on the current scale
+        let sigma = (number_of_scales as f32).sqrt() * SCALE_SIGMA_FACTOR;
+        let mut target: Vec<Complex<f32>> = (0..number_of_scales)
+            .map(|n| {
+                let dist = n as f32 - center;
+                Complex::new((-0.5 * (dist * dist) / (sigma * sigma)).exp(), 0.0)
+            })
+            .collect();
+        fft.process(&mut target);
+
+        // shrink the scale model if the tracking window is large, features of every pixel are not needed
+        let area = (window_width * window_height) as f32;
+        let model_factor = if area > SCALE_MODEL_MAX_AREA {
+            (SCALE_MODEL_MAX_AREA / area).sqrt()
+        } else {
+            1.0
+        };
+        let model_width = ((window_width as f32 * model_factor) as u32).max(2);
+        let model_height = ((window_height as f32 * model_factor) as u32).max(2);
+
+        // restrict the scale to a whole number of scale steps away from the initial scale
+        let step_ln = settings.scale_step.ln();
+        let min_ratio = (MIN_SCALED_WINDOW_SIZE / window_width as f32)
+            .max(MIN_SCALED_WINDOW_SIZE / window_height as f32);
+        let max_ratio = (frame_width as f32 / window_width as f32)
+            .min(frame_height as f32 / window_height as f32);
+        let (min_scale, max_scale) = if step_ln > 0.0 {
+            (
+                settings.scale_step.powf((min_ratio.ln() / step_ln).ceil()),
+                settings.scale_step.powf((max_ratio.ln() / step_ln).floor()),
+            )
+        } else {
+            (1.0, 1.0)
+        };
+
+        ScaleFilter {
+            scale_factors,
+            scale_window,
+            target,
+            top: Vec::new(),
+            bottom: vec![Complex::zero(); number_of_scales],
+            model_width,
+            model_height,
+            min_scale: min_scale.min(1.0),
+            max_scale: max_scale.max(1.0),
+            eta: settings.learning_rate,
+            border_mode,
+            fft,
+            inv_fft,
+        }
+    }
+
+    // Sample the frame at all scales around the current one, and return the features transformed along the scale dimension.
+    // The returned matrix has a row for every feature, and a column for every scale.
+    fn sample<F: Frame>(
+        &self,
+        frame: &F,
+        center: (f32, f32),
+        window_width: u32,
+        window_height: u32,
+        current_scale: f32,
+    ) -> Vec<Vec<Complex<f32>>> {
+        let number_of_scales = self.scale_factors.len();
+        let feature_count = (self.model_width * self.model_height) as usize;
+        let mut features = vec![vec![Complex::zero(); number_of_scales]; feature_count];
+
+        for (n, (factor, weight)) in self
+            .scale_factors
+            .iter()
+            .zip(&self.scale_window)
+            .enumerate()
+        {
+            let patch = scaled_window_crop(
+                frame,
+                window_width,
+                window_height,
+                current_scale * factor,
+                center,
+                self.border_mode,
+            );
+            let patch = imageops::resize(
+                &patch,
+                self.model_width,
+                self.model_height,
+                FilterType::Triangle,
+            );
+
+            for (row, value) in features
+                .iter_mut()
+                .zip(log_normalize(&imageops::grayscale(&patch)))
+            {
+                row[n] = Complex::new(value * weight, 0.0);
+            }
+        }
+
+        for row in features.iter_mut() {
+            self.fft.process(row);
+        }
+
+        features
+    }
+
+    pub(crate) fn train<F: Frame>(
+        &mut self,
+        frame: &F,
+        center: (f32, f32),
+        window_width: u32,
+        window_height: u32,
+        current_scale: f32,
+    ) {
+        let features = self.sample(frame, center, window_width, window_height, current_scale);
+
+        self.bottom = vec![Complex::zero(); self.scale_factors.len()];
+        for row in &features {
+            for (b, f) in self.bottom.iter_mut().zip(row) {
+                *b += f * f.conj();
+            }
+        }
+
+        self.top = features
+            .iter()
+            .map(|row| {
+                self.target
+                    .iter()
+                    .zip(row)
+                    .map(|(g, f)| g * f.conj())
+                    .collect()
+            })
+            .collect();
+    }
+
+    pub(crate) fn update<F: Frame>(
+        &mut self,
+        frame: &F,
+        center: (f32, f32),
+        window_width: u32,
+        window_height: u32,
+        current_scale: f32,
+    ) {
+        let features = self.sample(frame, center, window_width, window_height, current_scale);
+        let one_minus_eta = 1.0 - self.eta;
+
+        self.bottom.iter_mut().for_each(|b| *b *= one_minus_eta);
+        for row in &features {
+            for (b, f) in self.bottom.iter_mut().zip(row) {
+                *b += self.eta * (f * f.conj());
+            }
+        }
+
+        for (top_row, row) in self.top.iter_mut().zip(&features) {
+            for ((a, g), f) in top_row.iter_mut().zip(&self.target).zip(row) {
+                *a = self.eta * (g * f.conj()) + one_minus_eta * *a;
+            }
+        }
+    }
+
+    // Estimate the scale of the target at the given location. Returns the new absolute scale.
+    pub(crate) fn estimate<F: Frame>(
+        &self,
+        frame: &F,
+        center: (f32, f32),
+        window_width: u32,
+        window_height: u32,
+        current_scale: f32,
+    ) -> f32 {
+        let features = self.sample(frame, center, window_width, window_height, current_scale);
+
+        // sum the correlations of all feature rows
+        let mut response = vec![Complex::zero(); self.scale_factors.len()];
+        for (top_row, row) in self.top.iter().zip(&features) {
+            for (r, (a, z)) in response.iter_mut().zip(top_row.iter().zip(row)) {
+                *r += a * z;
+            }
+        }
+        response
+            .iter_mut()
+            .zip(&self.bottom)
+            .for_each(|(r, b)| *r /= b + SCALE_REGULARIZATION);
+
+        self.inv_fft.process(&mut response);
+
+        let best = response
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.re.partial_cmp(&b.1.re).unwrap_or(Ordering::Equal))
+            .map(|(n, _)| n)
+            .unwrap_or(self.scale_factors.len() / 2);
+
+        (current_scale * self.scale_factors[best]).clamp(self.min_scale, self.max_scale)
+    }
+}
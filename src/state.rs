@@ -0,0 +1,404 @@
+// Checkpoints of trackers, so a restarted process can carry on tracking where it left off.
+//
+// A checkpoint holds the settings of every tracker and everything it has learned, but nothing that can be derived
+// from the settings, like the FFT plans: those are recreated when the checkpoint is loaded.
+// Checkpoints are stored either as JSON, or in a compact binary format: the magic bytes "MOSSE", the format version
+// as a little endian u32 and the MessagePack encoding of the state.
+
+use crate::motion::{KalmanFilter, KalmanState};
+use crate::{
+    DormantPoolSettings, DormantTracker, DuplicateSuppression, MosseError, MosseTracker,
+    MosseTrackerSettings, MultiMosseTracker, Target, TargetId, TrackPoint, TrackStatus,
+};
+use rustfft::num_complex::Complex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// the version of the checkpoint format. Checkpoints of other versions are rejected.
+// Version 2 added the per-target state of the multi-tracker (paused targets, missed detections and histories), its
+// duplicate suppression and ID allocation.
+pub const CHECKPOINT_VERSION: u32 = 2;
+
+const MAGIC: &[u8] = b"MOSSE";
+
+// a spectrum as pairs of real and imaginary parts
+type Spectrum = Vec<[f32; 2]>;
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint<T> {
+    version: u32,
+    state: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackerState {
+    settings: MosseTrackerSettings,
+    filter: Vec<Spectrum>,
+    last_top: Vec<Spectrum>,
+    last_bottom: Spectrum,
+    current_target_center: (f32, f32),
+    current_scale: f32,
+    last_psr: f32,
+    missed_frames: u32,
+    kernel_filter: Option<KernelFilterState>,
+    scale_filter: Option<ScaleFilterState>,
+    kalman_filter: Option<KalmanState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KernelFilterState {
+    alpha: Spectrum,
+    template: Vec<Vec<f32>>,
+    template_spectrum: Vec<Spectrum>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScaleFilterState {
+    top: Vec<Spectrum>,
+    bottom: Spectrum,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MultiTrackerState<Id> {
+    settings: MosseTrackerSettings,
+    desperation_level: u32,
+    dormant_pool: DormantPoolSettings,
+    frame_count: u64,
+    last_id: Option<Id>,
+    duplicate_suppression: Option<DuplicateSuppression>,
+    history_capacity: usize,
+    trackers: Vec<TargetState<Id>>,
+    dormant: Vec<DormantTrackerState<Id>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TargetState<Id> {
+    id: Id,
+    death_watch: u32,
+    paused: bool,
+    misses: u32,
+    tracker: TrackerState,
+    history: Vec<TrackPointState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DormantTrackerState<Id> {
+    id: Id,
+    tracker: TrackerState,
+    last_location: (f32, f32),
+    since: u64,
+    history: Vec<TrackPointState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrackPointState {
+    frame: u64,
+    location: (f32, f32),
+    psr: f32,
+    status: TrackStatus,
+}
+
+impl MosseTracker {
+    pub fn to_json(&self) -> Result<String, MosseError> {
+        to_json(self.state())
+    }
+
+    pub fn from_json(json: &str) -> Result<MosseTracker, MosseError> {
+        MosseTracker::from_state(from_json(json)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MosseError> {
+        to_bytes(self.state())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MosseTracker, MosseError> {
+        MosseTracker::from_state(from_bytes(bytes)?)
+    }
+
+    fn state(&self) -> TrackerState {
+        TrackerState {
+            settings: self.settings.clone(),
+            filter: self.filter.iter().map(|c| to_pairs(c)).collect(),
+            last_top: self.last_top.iter().map(|c| to_pairs(c)).collect(),
+            last_bottom: to_pairs(&self.last_bottom),
+            current_target_center: self.current_target_center,
+            current_scale: self.current_scale,
+            last_psr: self.last_psr,
+            missed_frames: self.missed_frames,
+            kernel_filter: self.kernel_filter.as_ref().map(|k| KernelFilterState {
+                alpha: to_pairs(&k.alpha),
+                template: k.template.clone(),
+                template_spectrum: k.template_spectrum.iter().map(|c| to_pairs(c)).collect(),
+            }),
+            scale_filter: self.scale_filter.as_ref().map(|s| ScaleFilterState {
+                top: s.top.iter().map(|c| to_pairs(c)).collect(),
+                bottom: to_pairs(&s.bottom),
+            }),
+            kalman_filter: self.kalman_filter.as_ref().map(|k| k.state()),
+        }
+    }
+
+    // Create a tracker from its settings, and restore what it had learned.
+    // The parts of the state must have the dimensions that follow from the settings.
+    fn from_state(state: TrackerState) -> Result<MosseTracker, MosseError> {
+        let mut tracker = MosseTracker::new(&state.settings)?;
+
+        tracker.filter = restore_spectra(state.filter, &tracker.filter)?;
+        tracker.last_top = restore_spectra(state.last_top, &tracker.last_top)?;
+        tracker.last_bottom = restore_spectrum(state.last_bottom, tracker.last_bottom.len())?;
+        tracker.current_target_center = state.current_target_center;
+        tracker.current_scale = state.current_scale;
+        tracker.last_psr = state.last_psr;
+        tracker.missed_frames = state.missed_frames;
+
+        match (&mut tracker.kernel_filter, state.kernel_filter) {
+            (Some(kernel_filter), Some(saved)) => {
+                // the template is empty until the filter is trained
+                if saved.template.len() != saved.template_spectrum.len()
+                    || saved.template.iter().any(|c| c.len() != tracker.fft.len())
+                {
+                    return Err(invalid("kernelized filter template"));
+                }
+                kernel_filter.alpha = restore_spectrum(saved.alpha, kernel_filter.alpha.len())?;
+                kernel_filter.template_spectrum = saved
+                    .template_spectrum
+                    .into_iter()
+                    .map(|c| restore_spectrum(c, tracker.fft.spectrum_len()))
+                    .collect::<Result<_, _>>()?;
+                kernel_filter.template = saved.template;
+            }
+            (None, None) => {}
+            _ => return Err(invalid("kernelized filter")),
+        }
+
+        match (&mut tracker.scale_filter, state.scale_filter) {
+            (Some(scale_filter), Some(saved)) => {
+                // there is a row per feature of the scale samples once the filter is trained
+                let scales = scale_filter.bottom.len();
+                scale_filter.top = saved
+                    .top
+                    .into_iter()
+                    .map(|row| restore_spectrum(row, scales))
+                    .collect::<Result<_, _>>()?;
+                scale_filter.bottom = restore_spectrum(saved.bottom, scales)?;
+            }
+            (None, None) => {}
+            _ => return Err(invalid("scale filter")),
+        }
+
+        // the Kalman filter is rebuilt from the motion model, with the saved state and covariance
+        tracker.kalman_filter = match (tracker.motion_model, state.kalman_filter) {
+            (Some(model), Some(saved)) => Some(
+                KalmanFilter::restore(model, saved).ok_or_else(|| invalid("motion model state"))?,
+            ),
+            (None, None) => None,
+            _ => return Err(invalid("motion model")),
+        };
+
+        Ok(tracker)
+    }
+}
+
+// Multi-trackers with other ID types can be checkpointed if their IDs can be serialized, and are restored like
+// `MultiMosseTracker::<u64>::from_json(json)`.
+impl<Id: TargetId + Serialize + DeserializeOwned> MultiMosseTracker<Id> {
+    pub fn to_json(&self) -> Result<String, MosseError> {
+        to_json(self.state())
+    }
+
+    pub fn from_json(json: &str) -> Result<MultiMosseTracker<Id>, MosseError> {
+        MultiMosseTracker::from_state(from_json(json)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MosseError> {
+        to_bytes(self.state())
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MultiMosseTracker<Id>, MosseError> {
+        MultiMosseTracker::from_state(from_bytes(bytes)?)
+    }
+
+    fn state(&self) -> MultiTrackerState<Id> {
+        MultiTrackerState {
+            settings: self.settings.clone(),
+            desperation_level: self.desperation_level,
+            dormant_pool: self.dormant_pool,
+            frame_count: self.frame_count,
+            last_id: self.last_id,
+            duplicate_suppression: self.duplicate_suppression,
+            history_capacity: self.history_capacity,
+            trackers: self
+                .trackers
+                .iter()
+                .map(|target| TargetState {
+                    id: target.id,
+                    death_watch: target.death_watch,
+                    paused: target.paused,
+                    misses: target.misses,
+                    tracker: target.tracker.state(),
+                    history: history_state(&target.history),
+                })
+                .collect(),
+            dormant: self
+                .dormant
+                .iter()
+                .map(|dormant| DormantTrackerState {
+                    id: dormant.id,
+                    tracker: dormant.tracker.state(),
+                    last_location: dormant.last_location,
+                    since: dormant.since,
+                    history: history_state(&dormant.history),
+                })
+                .collect(),
+        }
+    }
+
+    fn from_state(state: MultiTrackerState<Id>) -> Result<MultiMosseTracker<Id>, MosseError> {
+        let mut multi_tracker = MultiMosseTracker::with_dormant_pool(
+            state.settings,
+            state.desperation_level,
+            state.dormant_pool,
+        );
+        multi_tracker.frame_count = state.frame_count;
+        multi_tracker.last_id = state.last_id;
+        multi_tracker.duplicate_suppression = state.duplicate_suppression;
+        multi_tracker.history_capacity = state.history_capacity;
+        multi_tracker.trackers = state
+            .trackers
+            .into_iter()
+            .map(|target| {
+                Ok(Target {
+                    id: target.id,
+                    death_watch: target.death_watch,
+                    paused: target.paused,
+                    misses: target.misses,
+                    tracker: MosseTracker::from_state(target.tracker)?,
+                    history: restore_history(target.history),
+                })
+            })
+            .collect::<Result<_, MosseError>>()?;
+        multi_tracker.dormant = state
+            .dormant
+            .into_iter()
+            .map(|dormant| {
+                Ok(DormantTracker {
+                    id: dormant.id,
+                    tracker: MosseTracker::from_state(dormant.tracker)?,
+                    last_location: dormant.last_location,
+                    since: dormant.since,
+                    history: restore_history(dormant.history),
+                })
+            })
+            .collect::<Result<_, MosseError>>()?;
+        Ok(multi_tracker)
+    }
+}
+
+fn history_state(history: &VecDeque<TrackPoint>) -> Vec<TrackPointState> {
+    history
+        .iter()
+        .map(|point| TrackPointState {
+            frame: point.frame,
+            location: point.location,
+            psr: point.psr,
+            status: point.status,
+        })
+        .collect()
+}
+
+fn restore_history(history: Vec<TrackPointState>) -> VecDeque<TrackPoint> {
+    history
+        .into_iter()
+        .map(|point| TrackPoint {
+            frame: point.frame,
+            location: point.location,
+            psr: point.psr,
+            status: point.status,
+        })
+        .collect()
+}
+
+fn to_json<T: Serialize>(state: T) -> Result<String, MosseError> {
+    let checkpoint = Checkpoint {
+        version: CHECKPOINT_VERSION,
+        state,
+    };
+    serde_json::to_string(&checkpoint).map_err(|e| MosseError::InvalidCheckpoint(e.to_string()))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, MosseError> {
+    // check the version before the state, whose layout depends on it
+    let mut checkpoint: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| MosseError::InvalidCheckpoint(e.to_string()))?;
+    let version = checkpoint.get("version").and_then(|v| v.as_u64());
+    if version != Some(CHECKPOINT_VERSION as u64) {
+        return Err(MosseError::UnsupportedCheckpointVersion(
+            version.map_or(0, |v| v as u32),
+        ));
+    }
+    serde_json::from_value(checkpoint["state"].take())
+        .map_err(|e| MosseError::InvalidCheckpoint(e.to_string()))
+}
+
+fn to_bytes<T: Serialize>(state: T) -> Result<Vec<u8>, MosseError> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+    // struct fields are stored by name, as the settings skip the fields that are not set
+    rmp_serde::encode::write_named(&mut bytes, &state)
+        .map_err(|e| MosseError::InvalidCheckpoint(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MosseError> {
+    let header = MAGIC.len() + 4;
+    if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(MosseError::InvalidCheckpoint(
+            "not a tracker checkpoint".to_string(),
+        ));
+    }
+    let version = u32::from_le_bytes([
+        bytes[MAGIC.len()],
+        bytes[MAGIC.len() + 1],
+        bytes[MAGIC.len() + 2],
+        bytes[MAGIC.len() + 3],
+    ]);
+    if version != CHECKPOINT_VERSION {
+        return Err(MosseError::UnsupportedCheckpointVersion(version));
+    }
+    rmp_serde::from_slice(&bytes[header..])
+        .map_err(|e| MosseError::InvalidCheckpoint(e.to_string()))
+}
+
+fn to_pairs(spectrum: &[Complex<f32>]) -> Spectrum {
+    spectrum.iter().map(|c| [c.re, c.im]).collect()
+}
+
+fn restore_spectrum(pairs: Spectrum, len: usize) -> Result<Vec<Complex<f32>>, MosseError> {
+    if pairs.len() != len {
+        return Err(invalid("spectrum"));
+    }
+    Ok(pairs
+        .into_iter()
+        .map(|[re, im]| Complex::new(re, im))
+        .collect())
+}
+
+// restore a spectrum per channel, with the same dimensions as the spectra of a new tracker
+fn restore_spectra(
+    saved: Vec<Spectrum>,
+    expected: &[Vec<Complex<f32>>],
+) -> Result<Vec<Vec<Complex<f32>>>, MosseError> {
+    if saved.len() != expected.len() {
+        return Err(invalid("number of channels"));
+    }
+    saved
+        .into_iter()
+        .zip(expected)
+        .map(|(pairs, channel)| restore_spectrum(pairs, channel.len()))
+        .collect()
+}
+
+fn invalid(part: &str) -> MosseError {
+    MosseError::InvalidCheckpoint(format!("the {} does not match the settings", part))
+}
@@ -8,40 +8,60 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen]
 pub struct MultiMosseTrackerJS {
     tracker: MultiMosseTracker,
+    // the ID of the target that set_target() moves, once it has been set
+    main_target: Option<u32>,
 }
 
 #[wasm_bindgen]
 impl MultiMosseTrackerJS {
     #[wasm_bindgen(constructor)]
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32) -> Result<MultiMosseTrackerJS, JsValue> {
         let window_size = 48;
-        let psr_threshold = 7.0;
-        let settings = MosseTrackerSettings {
-            window_size,
-            width,
-            height,
-            regularization: 0.002,
-            learning_rate: 0.05,
-            psr_threshold,
-        };
+        let settings = MosseTrackerSettings::builder(width, height)
+            .window_size(window_size, window_size)
+            .regularization(0.002)
+            .build()
+            .map_err(to_js_error)?;
         let desperation_threshold = 4;
         let multi_tracker = MultiMosseTracker::new(settings, desperation_threshold);
-        Self {
+        Ok(Self {
             tracker: multi_tracker,
+            main_target: None,
+        })
+    }
+
+    // set or move the main target, which gets an ID of its own the first time
+    #[wasm_bindgen]
+    pub fn set_target(&mut self, x: u32, y: u32, img_data: &[u8]) -> Result<(), JsValue> {
+        let img = image::load_from_memory_with_format(img_data, image::ImageFormat::Png)
+            .map_err(to_js_error)?;
+        let coords = (x as f32, y as f32);
+        let id = match self.main_target {
+            Some(id) => self
+                .tracker
+                .add_or_replace_target(id, coords, &img.to_rgb8()),
+            None => self.tracker.add_target(coords, &img.to_rgb8()),
         }
+        .map_err(to_js_error)?;
+        self.main_target = Some(id);
+        Ok(())
     }
 
+    // track another object, under a new ID which is returned
     #[wasm_bindgen]
-    pub fn set_target(&mut self, x: u32, y: u32, img_data: &[u8]) {
-        let img = image::load_from_memory_with_format(img_data, image::ImageFormat::Png).unwrap();
+    pub fn add_target(&mut self, x: u32, y: u32, img_data: &[u8]) -> Result<u32, JsValue> {
+        let img = image::load_from_memory_with_format(img_data, image::ImageFormat::Png)
+            .map_err(to_js_error)?;
         self.tracker
-            .add_or_replace_target(1, (x, y), &img.to_luma8());
+            .add_target((x as f32, y as f32), &img.to_rgb8())
+            .map_err(to_js_error)
     }
 
     #[wasm_bindgen]
-    pub fn track(&mut self, img_data: &[u8]) -> Vec<u8> {
-        let image = image::load_from_memory_with_format(img_data, image::ImageFormat::Png).unwrap();
-        let predictions = self.tracker.track(&image.to_luma8());
+    pub fn track(&mut self, img_data: &[u8]) -> Result<Vec<u8>, JsValue> {
+        let image = image::load_from_memory_with_format(img_data, image::ImageFormat::Png)
+            .map_err(to_js_error)?;
+        let predictions = self.tracker.track(&image.to_rgb8()).map_err(to_js_error)?;
         let mut img_copy = image.to_rgba8();
         for (obj_id, pred) in predictions.iter() {
             let mut color = Rgba([125u8, 255u8, 0u8, 0u8]);
@@ -54,14 +74,14 @@ impl MultiMosseTrackerJS {
                 pred.location.0 as i32,
                 pred.location.1 as i32,
             );
-            let window_size = self.tracker.settings.window_size;
+            let window_width = self.tracker.settings.window_width;
+            let window_height = self.tracker.settings.window_height;
+            // top left corner of the tracking window
+            let left = (pred.location.0 - (window_width / 2) as f32) as i32;
+            let top = (pred.location.1 - (window_height / 2) as f32) as i32;
             draw_hollow_rect_mut(
                 &mut img_copy,
-                Rect::at(
-                    pred.location.0.saturating_sub(window_size / 2) as i32,
-                    pred.location.1.saturating_sub(window_size / 2) as i32,
-                )
-                .of_size(window_size, window_size),
+                Rect::at(left.max(0), top.max(0)).of_size(window_width, window_height),
                 color,
             );
 
@@ -73,8 +93,8 @@ impl MultiMosseTrackerJS {
             draw_text_mut(
                 &mut img_copy,
                 Rgba([125u8, 255u8, 0u8, 0u8]),
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2)).try_into().unwrap(),
+                left,
+                top,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("#{}", obj_id),
@@ -83,10 +103,8 @@ impl MultiMosseTrackerJS {
             draw_text_mut(
                 &mut img_copy,
                 color,
-                (pred.location.0 - (window_size / 2)).try_into().unwrap(),
-                (pred.location.1 - (window_size / 2) + FONT_SCALE as u32)
-                    .try_into()
-                    .unwrap(),
+                left,
+                top + FONT_SCALE as i32,
                 Scale::uniform(FONT_SCALE),
                 &font,
                 &format!("PSR: {:.2}", pred.psr),
@@ -99,7 +117,12 @@ impl MultiMosseTrackerJS {
                 &mut std::io::Cursor::new(&mut image_data),
                 image::ImageFormat::Png,
             )
-            .unwrap();
-        image_data
+            .map_err(to_js_error)?;
+        Ok(image_data)
     }
 }
+
+// errors are passed to JavaScript as exceptions with the error message
+fn to_js_error<E: std::fmt::Display>(error: E) -> JsValue {
+    JsValue::from_str(&error.to_string())
+}